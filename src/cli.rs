@@ -0,0 +1,179 @@
+//! Command line subcommands that run without starting the GUI
+
+use std::{borrow::Cow, io::Write, process::ExitCode};
+
+use serde::Serialize;
+
+use crate::{
+    config_diff::{ConfigDiff, EntryChange},
+    config_file::{ConfigFile, DualRoleConfig, RemapConfig},
+    deviceinfo::{DeviceInfo, input_dir},
+};
+
+const DEVICES_USAGE: &str = "Usage: evremap-gtk devices [--json]";
+const DIFF_USAGE: &str = "Usage: evremap-gtk diff [--json] <old.toml> <new.toml>";
+
+/// Print the list of connected input devices to stdout.
+///
+/// The plain format is one device per line with tab-separated fields: path, whether the device
+/// supports remapping (`yes`/`no`), phys (`-` if missing) and name. The name is last since it's
+/// the only field that may contain whitespace.
+pub fn list_devices(args: &[String]) -> ExitCode {
+    let json = match args {
+        [] => false,
+        [flag] if flag == "--json" => true,
+        _ => {
+            eprintln!("{DEVICES_USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let devices = match DeviceInfo::obtain_device_list() {
+        Ok(scan) => scan.devices,
+        Err(e) => {
+            eprintln!("Failed to list devices: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if devices.is_empty() {
+        eprintln!(
+            "No input devices could be opened. Reading {}/event* usually requires root or \
+             membership in the \"input\" group.",
+            input_dir().display()
+        );
+        return ExitCode::FAILURE;
+    }
+
+    if json {
+        let devices: Vec<DeviceJson> = devices.iter().map(DeviceJson::from).collect();
+        if let Err(e) = print_json(&devices) {
+            eprintln!("Failed to write the device list: {e}");
+            return ExitCode::FAILURE;
+        }
+    } else {
+        for dev in &devices {
+            println!(
+                "{}\t{}\t{}\t{}",
+                dev.path.display(),
+                if dev.supports_remap { "yes" } else { "no" },
+                dev.phys.as_deref().unwrap_or("-"),
+                dev.name
+            );
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// JSON form of a device in `devices --json`
+#[derive(Serialize)]
+struct DeviceJson<'a> {
+    name: &'a str,
+    phys: Option<&'a str>,
+    path: Cow<'a, str>,
+    supports_remap: bool,
+}
+
+impl<'a> From<&'a DeviceInfo> for DeviceJson<'a> {
+    fn from(dev: &'a DeviceInfo) -> Self {
+        Self {
+            name: &dev.name,
+            phys: dev.phys.as_deref(),
+            path: dev.path.to_string_lossy(),
+            supports_remap: dev.supports_remap,
+        }
+    }
+}
+
+/// Print the structural differences between two config files to stdout.
+///
+/// Exits with 0 if the configs are equivalent, 1 if they differ and 2 on errors, like `diff`.
+pub fn diff_configs(args: &[String]) -> ExitCode {
+    let (json, paths) = match args {
+        [flag, rest @ ..] if flag == "--json" => (true, rest),
+        rest => (false, rest),
+    };
+    let [old_path, new_path] = paths else {
+        eprintln!("{DIFF_USAGE}");
+        return ExitCode::from(2);
+    };
+
+    let read = |path: &String| {
+        ConfigFile::read_from(path).inspect_err(|e| eprintln!("Failed to read {path}: {e}"))
+    };
+    let (Ok(old), Ok(new)) = (read(old_path), read(new_path)) else {
+        return ExitCode::from(2);
+    };
+
+    let diff = ConfigDiff::new(&old, &new);
+    if json {
+        if let Err(e) = print_json(&DiffJson::from(&diff)) {
+            eprintln!("Failed to write the differences: {e}");
+            return ExitCode::from(2);
+        }
+    } else {
+        print!("{diff}");
+    }
+    if diff.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// JSON form of a [`ConfigDiff`] in `diff --json`
+#[derive(Serialize)]
+struct DiffJson<'a> {
+    device_name: Option<ValueChangeJson<'a>>,
+    phys: Option<ValueChangeJson<'a>>,
+    remap: Vec<EntryChangeJson<'a, RemapConfig>>,
+    dual_role: Vec<EntryChangeJson<'a, DualRoleConfig>>,
+}
+
+#[derive(Serialize)]
+struct ValueChangeJson<'a> {
+    old: Option<&'a str>,
+    new: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "change", rename_all = "lowercase")]
+enum EntryChangeJson<'a, T> {
+    Added { new: &'a T },
+    Removed { old: &'a T },
+    Changed { old: &'a T, new: &'a T },
+}
+
+impl<'a> From<&'a ConfigDiff> for DiffJson<'a> {
+    fn from(diff: &'a ConfigDiff) -> Self {
+        let value_change = |change: &'a Option<(Option<String>, Option<String>)>| {
+            change.as_ref().map(|(old, new)| ValueChangeJson {
+                old: old.as_deref(),
+                new: new.as_deref(),
+            })
+        };
+        Self {
+            device_name: value_change(&diff.device_name),
+            phys: value_change(&diff.phys),
+            remap: diff.remap.iter().map(EntryChangeJson::from).collect(),
+            dual_role: diff.dual_role.iter().map(EntryChangeJson::from).collect(),
+        }
+    }
+}
+
+impl<'a, T> From<&'a EntryChange<T>> for EntryChangeJson<'a, T> {
+    fn from(change: &'a EntryChange<T>) -> Self {
+        match change {
+            EntryChange::Added(new) => Self::Added { new },
+            EntryChange::Removed(old) => Self::Removed { old },
+            EntryChange::Changed { old, new } => Self::Changed { old, new },
+        }
+    }
+}
+
+/// Write `value` to stdout as pretty-printed JSON followed by a newline
+fn print_json(value: &impl Serialize) -> Result<(), serde_json::Error> {
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, value)?;
+    writeln!(stdout).map_err(serde_json::Error::io)
+}
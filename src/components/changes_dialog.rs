@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+
+use relm4::{adw::prelude::*, prelude::*};
+
+use crate::config_diff::{DiffLine, diff_lines};
+
+/// Line diff of the saved file and the config as it would be saved
+#[derive(Debug)]
+pub struct ChangesDialog {
+    title: String,
+    buffer: gtk::TextBuffer,
+    removed_tag: gtk::TextTag,
+    added_tag: gtk::TextTag,
+}
+
+#[derive(Debug)]
+pub enum ChangesMsg {
+    /// Show the changes from `old`, the contents of the file at `path`, to `new`
+    SetChanges {
+        path: PathBuf,
+        old: String,
+        new: String,
+    },
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for ChangesDialog {
+    type Init = ();
+    type Input = ChangesMsg;
+    type Output = ();
+
+    view! {
+        adw::Dialog {
+            #[watch]
+            set_title: &model.title,
+            set_content_width: 600,
+            set_content_height: 500,
+
+            #[wrap(Some)]
+            set_child = &adw::ToolbarView {
+                add_top_bar = &adw::HeaderBar {},
+
+                #[wrap(Some)]
+                set_content = &gtk::ScrolledWindow {
+                    set_vexpand: true,
+
+                    gtk::TextView {
+                        set_buffer: Some(&model.buffer),
+                        set_editable: false,
+                        set_cursor_visible: false,
+                        set_monospace: true,
+                        set_top_margin: 12,
+                        set_bottom_margin: 12,
+                        set_left_margin: 12,
+                        set_right_margin: 12,
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = Self {
+            title: "Changes".to_owned(),
+            buffer: gtk::TextBuffer::new(None),
+            removed_tag: gtk::TextTag::builder()
+                .paragraph_background_rgba(&gtk::gdk::RGBA::new(0.88, 0.11, 0.14, 0.2))
+                .build(),
+            added_tag: gtk::TextTag::builder()
+                .paragraph_background_rgba(&gtk::gdk::RGBA::new(0.18, 0.76, 0.49, 0.2))
+                .build(),
+        };
+        model.buffer.tag_table().add(&model.removed_tag);
+        model.buffer.tag_table().add(&model.added_tag);
+        let widgets = view_output!();
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>) {
+        match message {
+            ChangesMsg::SetChanges { path, old, new } => {
+                self.title = match path.file_name() {
+                    Some(name) => format!("Changes to {}", name.to_string_lossy()),
+                    None => "Changes".to_owned(),
+                };
+                self.buffer.set_text("");
+                for line in diff_lines(&old, &new) {
+                    let tags: &[&gtk::TextTag] = match line {
+                        DiffLine::Same(_) => &[],
+                        DiffLine::Removed(_) => &[&self.removed_tag],
+                        DiffLine::Added(_) => &[&self.added_tag],
+                    };
+                    self.buffer.insert_with_tags(
+                        &mut self.buffer.end_iter(),
+                        &format!("{line}\n"),
+                        tags,
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use gtk::gdk;
+use relm4::{abstractions::Toaster, adw::prelude::*, prelude::*};
+
+/// Read-only view of the config as it would be saved
+#[derive(Debug)]
+pub struct ConfigPreviewDialog {
+    contents: String,
+    buffer: gtk::TextBuffer,
+    toaster: Toaster,
+}
+
+#[derive(Debug)]
+pub enum ConfigPreviewMsg {
+    /// Replace the shown config with the newly generated one
+    SetContents(String),
+    CopyToClipboard,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for ConfigPreviewDialog {
+    type Init = ();
+    type Input = ConfigPreviewMsg;
+    type Output = ();
+
+    view! {
+        adw::Dialog {
+            set_title: "Config preview",
+            set_content_width: 600,
+            set_content_height: 500,
+
+            #[wrap(Some)]
+            set_child = &adw::ToolbarView {
+                add_top_bar = &adw::HeaderBar {
+                    pack_start = &gtk::Button::from_icon_name("edit-copy-symbolic") {
+                        set_tooltip_text: Some("Copy to clipboard"),
+                        connect_clicked => ConfigPreviewMsg::CopyToClipboard,
+                    },
+                },
+
+                #[local_ref]
+                #[wrap(Some)]
+                set_content = toast_overlay -> adw::ToastOverlay {
+                    gtk::ScrolledWindow {
+                        set_vexpand: true,
+
+                        gtk::TextView {
+                            set_buffer: Some(&model.buffer),
+                            set_editable: false,
+                            set_cursor_visible: false,
+                            set_monospace: true,
+                            set_top_margin: 12,
+                            set_bottom_margin: 12,
+                            set_left_margin: 12,
+                            set_right_margin: 12,
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = Self {
+            contents: String::new(),
+            buffer: gtk::TextBuffer::new(None),
+            toaster: Toaster::default(),
+        };
+        let toast_overlay = model.toaster.overlay_widget();
+        let widgets = view_output!();
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>) {
+        match message {
+            ConfigPreviewMsg::SetContents(contents) => {
+                self.buffer.set_text(&contents);
+                self.contents = contents;
+            }
+            ConfigPreviewMsg::CopyToClipboard => {
+                if let Some(display) = gdk::Display::default() {
+                    display.clipboard().set_text(&self.contents);
+                    self.toaster
+                        .add_toast(adw::Toast::new("Copied the config to the clipboard"));
+                }
+            }
+        }
+    }
+}
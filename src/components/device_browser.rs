@@ -1,29 +1,206 @@
-use gtk::prelude::*;
+use gtk::{gdk, prelude::*};
 use relm4::prelude::*;
 
-use crate::deviceinfo::DeviceInfo;
+use crate::deviceinfo::{DeviceField, DeviceId, DeviceInfo, DeviceKindFilter};
+
+/// Put the value of the device field on the clipboard, returns whether there was anything to copy
+pub fn copy_device_field(device: &DeviceInfo, field: DeviceField) -> bool {
+    match (device.field(field), gdk::Display::default()) {
+        (Some(value), Some(display)) => {
+            display.clipboard().set_text(&value);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Devices sharing a name, like the several event nodes of one keyboard. A lone device is shown
+/// as is, several ones under a header that can be expanded.
+#[derive(Debug)]
+pub struct DeviceGroup {
+    name: String,
+    devices: FactoryVecDeque<DeviceDisplay>,
+    filter: DeviceFilter,
+    /// The devices of the group, for hiding it when none of them match the filter
+    members: Vec<DeviceInfo>,
+    expanded: bool,
+}
+
+#[derive(Debug)]
+pub enum DeviceGroupMsg {
+    SetFilter(DeviceFilter),
+    ToggleExpanded,
+    /// Passed on to the browser, aliases are noted for filtering
+    Device(DeviceDisplayOutput),
+}
+
+impl DeviceGroup {
+    fn is_group(&self) -> bool {
+        self.members.len() > 1
+    }
+
+    fn any_visible(&self) -> bool {
+        self.members.iter().any(|d| self.filter.matches(d))
+    }
+}
+
+#[relm4::factory(pub)]
+impl FactoryComponent for DeviceGroup {
+    type Init = (Vec<DeviceInfo>, DeviceFilter);
+    type Input = DeviceGroupMsg;
+    type Output = DeviceDisplayOutput;
+    type CommandOutput = ();
+    type ParentWidget = gtk::Box;
+
+    view! {
+        #[root]
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 6,
+            #[watch]
+            set_visible: self.any_visible(),
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 6,
+                set_visible: self.is_group(),
+
+                gtk::Button {
+                    set_has_frame: false,
+                    #[watch]
+                    set_icon_name: if self.expanded { "pan-down-symbolic" } else { "pan-end-symbolic" },
+                    #[watch]
+                    set_tooltip_text: Some(if self.expanded { "Collapse" } else { "Expand" }),
+                    connect_clicked => DeviceGroupMsg::ToggleExpanded,
+                },
+
+                gtk::Label {
+                    set_label: &self.name,
+                    set_tooltip_text: Some(&self.name),
+                    set_halign: gtk::Align::Start,
+                    set_ellipsize: gtk::pango::EllipsizeMode::End,
+                    add_css_class: "heading",
+                },
+
+                gtk::Label {
+                    set_label: &format!("{} devices with this name", self.members.len()),
+                    set_halign: gtk::Align::Start,
+                    set_hexpand: true,
+                    add_css_class: "dim-label",
+                },
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 12,
+                set_margin_start: if self.is_group() { 24 } else { 0 },
+                #[watch]
+                set_visible: !self.is_group() || self.expanded,
+                append: self.devices.widget(),
+            },
+        }
+    }
+
+    fn init_model(init: Self::Init, _index: &Self::Index, sender: FactorySender<Self>) -> Self {
+        let (members, filter) = init;
+        let mut devices = FactoryVecDeque::builder()
+            .launch(gtk::Box::new(gtk::Orientation::Vertical, 12))
+            .forward(sender.input_sender(), DeviceGroupMsg::Device);
+        {
+            let mut guard = devices.guard();
+            for dev in &members {
+                guard.push_back((dev.clone(), filter.clone()));
+            }
+        }
+        Self {
+            name: members.first().map(|d| d.name.clone()).unwrap_or_default(),
+            devices,
+            filter,
+            members,
+            expanded: false,
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: FactorySender<Self>) {
+        match message {
+            DeviceGroupMsg::Device(out) => {
+                if let DeviceDisplayOutput::SetAlias { id, alias } = &out
+                    && let Some(dev) = self.members.iter_mut().find(|d| id.matches(d))
+                {
+                    dev.alias = alias.clone();
+                }
+                sender.output(out).unwrap();
+            }
+            DeviceGroupMsg::SetFilter(filter) => {
+                self.devices
+                    .broadcast(DeviceDisplayMsg::SetFilter(filter.clone()));
+                self.filter = filter;
+            }
+            DeviceGroupMsg::ToggleExpanded => self.expanded = !self.expanded,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct DeviceDisplay {
     device: DeviceInfo,
-    hidden: bool,
+    filter: DeviceFilter,
+}
+
+/// Which devices should be hidden from the browser
+#[derive(Debug, Clone, Default)]
+pub struct DeviceFilter {
+    pub kind: DeviceKindFilter,
+    /// Show devices created through uinput, they are hidden otherwise
+    pub show_virtual: bool,
+    /// Show only devices with this text in the name, phys or alias, ignoring case
+    pub text: String,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl DeviceFilter {
+    pub fn matches(&self, device: &DeviceInfo) -> bool {
+        self.kind.matches(device)
+            && (self.show_virtual || !device.is_virtual)
+            && self.matches_text(device)
+    }
+
+    fn matches_text(&self, device: &DeviceInfo) -> bool {
+        let text = self.text.trim().to_lowercase();
+        text.is_empty()
+            || [
+                Some(&device.name),
+                device.phys.as_ref(),
+                device.alias.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .any(|field| field.to_lowercase().contains(&text))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum DeviceDisplayMsg {
-    ShowHidden,
-    HideUseless,
+    SetFilter(DeviceFilter),
+    /// User finished editing the alias, empty text removes it
+    AliasEdited(String),
+    Copy(DeviceField),
 }
 
 #[derive(Debug)]
 pub enum DeviceDisplayOutput {
     SetDevice(DeviceInfo),
     UseDeviceInLogger(DeviceInfo),
+    SetAlias {
+        id: DeviceId,
+        alias: Option<String>,
+    },
+    /// A field of the device was put on the clipboard
+    Copied(DeviceField),
 }
 
 #[relm4::factory(pub)]
 impl FactoryComponent for DeviceDisplay {
-    type Init = DeviceInfo;
+    type Init = (DeviceInfo, DeviceFilter);
     type Input = DeviceDisplayMsg;
     type Output = DeviceDisplayOutput;
     type CommandOutput = ();
@@ -33,7 +210,7 @@ impl FactoryComponent for DeviceDisplay {
         #[root]
         gtk::Frame {
             #[watch]
-            set_visible: !self.hidden || self.device.supports_remap,
+            set_visible: self.filter.matches(&self.device),
             set_hexpand: true,
 
             gtk::Grid {
@@ -42,11 +219,39 @@ impl FactoryComponent for DeviceDisplay {
                 set_column_spacing: 12,
 
                 attach[0,0,1,1] = &gtk::Label {
+                    set_label: "Alias:",
+                    set_halign: gtk::Align::Start,
+                },
+
+                attach[1,0,1,1] = &gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 6,
+
+                    #[name(alias_label)]
+                    gtk::EditableLabel {
+                        set_text: self.device.alias.as_deref().unwrap_or(""),
+                        set_tooltip_text: Some("Cosmetic name, doesn't affect the config"),
+                        set_hexpand: true,
+                        connect_editing_notify[sender] => move |el| {
+                            if !el.is_editing() {
+                                sender.input(DeviceDisplayMsg::AliasEdited(el.text().to_string()));
+                            }
+                        }
+                    },
+
+                    gtk::Button::from_icon_name("document-edit-symbolic") {
+                        set_tooltip_text: Some("Edit alias"),
+                        set_has_frame: false,
+                        connect_clicked[alias_label] => move |_| alias_label.start_editing(),
+                    },
+                },
+
+                attach[0,1,1,1] = &gtk::Label {
                     set_label: "Device name:",
                     set_halign: gtk::Align::Start,
                 },
 
-                attach[1,0,1,1] = &gtk::Label {
+                attach[1,1,1,1] = &gtk::Label {
                     set_label: &self.device.name,
                     set_tooltip_text: Some(&self.device.name),
                     set_selectable: true,
@@ -55,12 +260,18 @@ impl FactoryComponent for DeviceDisplay {
                     set_ellipsize: gtk::pango::EllipsizeMode::End,
                 },
 
-                attach[0,1,1,1] = &gtk::Label {
+                attach[2,1,1,1] = &gtk::Button::from_icon_name("edit-copy-symbolic") {
+                    set_tooltip_text: Some("Copy name"),
+                    set_has_frame: false,
+                    connect_clicked => DeviceDisplayMsg::Copy(DeviceField::Name),
+                },
+
+                attach[0,2,1,1] = &gtk::Label {
                     set_label: "Device phys:",
                     set_halign: gtk::Align::Start,
                 },
 
-                attach[1,1,1,1] = &gtk::Label {
+                attach[1,2,1,1] = &gtk::Label {
                     set_label: self.device.phys.as_ref().map_or("(Missing)", |v| v),
                     set_tooltip_text: self.device.phys.as_deref(),
                     set_selectable: self.device.phys.is_some(),
@@ -69,12 +280,19 @@ impl FactoryComponent for DeviceDisplay {
                     set_ellipsize: gtk::pango::EllipsizeMode::End,
                 },
 
-                attach[0,2,1,1] = &gtk::Label {
+                attach[2,2,1,1] = &gtk::Button::from_icon_name("edit-copy-symbolic") {
+                    set_tooltip_text: Some("Copy phys"),
+                    set_has_frame: false,
+                    set_sensitive: self.device.phys.is_some(),
+                    connect_clicked => DeviceDisplayMsg::Copy(DeviceField::Phys),
+                },
+
+                attach[0,3,1,1] = &gtk::Label {
                     set_label: "Device path:",
                     set_halign: gtk::Align::Start,
                 },
 
-                attach[1,2,1,1] = &gtk::Label {
+                attach[1,3,1,1] = &gtk::Label {
                     set_label: &format!("{}", self.device.path.display()),
                     set_tooltip_text: Some(&format!("{}", self.device.path.display())),
                     set_selectable: self.device.phys.is_some(),
@@ -83,14 +301,45 @@ impl FactoryComponent for DeviceDisplay {
                     set_ellipsize: gtk::pango::EllipsizeMode::End,
                 },
 
-                attach[2,0,1,3] = &gtk::Button::from_icon_name("object-select-symbolic") {
+                attach[2,3,1,1] = &gtk::Button::from_icon_name("edit-copy-symbolic") {
+                    set_tooltip_text: Some("Copy path"),
+                    set_has_frame: false,
+                    connect_clicked => DeviceDisplayMsg::Copy(DeviceField::Path),
+                },
+
+                attach[0,4,1,1] = &gtk::Label {
+                    set_label: "IDs:",
+                    set_halign: gtk::Align::Start,
+                },
+
+                attach[1,4,1,1] = &gtk::Label {
+                    set_label: &format!("{} on {}", self.device.vid_pid(), self.device.bus_name()),
+                    set_tooltip_text: Some("Vendor ID:product ID and the bus the device is connected through"),
+                    set_selectable: true,
+                    set_halign: gtk::Align::Start,
+                    set_hexpand: true,
+                },
+
+                attach[0,5,1,1] = &gtk::Label {
+                    set_label: "Type:",
+                    set_halign: gtk::Align::Start,
+                },
+
+                attach[1,5,1,1] = &gtk::Label {
+                    set_label: self.device.key_capability().label(),
+                    set_tooltip_text: Some("What the device can do with keys, going by the events it reports"),
+                    set_halign: gtk::Align::Start,
+                    set_hexpand: true,
+                },
+
+                attach[3,0,1,6] = &gtk::Button::from_icon_name("object-select-symbolic") {
                     set_tooltip_text: Some("Use this device"),
                     connect_clicked[sender, device_cl = self.device.clone()] => move |_| {
                         sender.output(DeviceDisplayOutput::SetDevice(device_cl.clone())).unwrap();
                     }
                 },
 
-                attach[3,0,1,3] = &gtk::Button::from_icon_name("view-paged-symbolic") {
+                attach[4,0,1,6] = &gtk::Button::from_icon_name("view-paged-symbolic") {
                     set_tooltip_text: Some("See device events"),
                     connect_clicked[sender, device_cl = self.device.clone()] => move |_| {
                         sender.output(DeviceDisplayOutput::UseDeviceInLogger(device_cl.clone())).unwrap();
@@ -101,16 +350,30 @@ impl FactoryComponent for DeviceDisplay {
     }
 
     fn init_model(init: Self::Init, _index: &Self::Index, _sender: FactorySender<Self>) -> Self {
-        Self {
-            device: init,
-            hidden: true,
-        }
+        let (device, filter) = init;
+        Self { device, filter }
     }
 
-    fn update(&mut self, message: Self::Input, _sender: FactorySender<Self>) {
+    fn update(&mut self, message: Self::Input, sender: FactorySender<Self>) {
         match message {
-            DeviceDisplayMsg::ShowHidden => self.hidden = false,
-            DeviceDisplayMsg::HideUseless => self.hidden = true,
+            DeviceDisplayMsg::SetFilter(filter) => self.filter = filter,
+            DeviceDisplayMsg::Copy(field) => {
+                if copy_device_field(&self.device, field) {
+                    sender.output(DeviceDisplayOutput::Copied(field)).unwrap();
+                }
+            }
+            DeviceDisplayMsg::AliasEdited(text) => {
+                let alias = Some(text.trim().to_owned()).filter(|a| !a.is_empty());
+                if alias != self.device.alias {
+                    self.device.alias = alias.clone();
+                    sender
+                        .output(DeviceDisplayOutput::SetAlias {
+                            id: DeviceId::of(&self.device),
+                            alias,
+                        })
+                        .unwrap();
+                }
+            }
         }
     }
 }
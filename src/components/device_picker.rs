@@ -0,0 +1,202 @@
+use relm4::{adw::prelude::*, prelude::*};
+
+use crate::deviceinfo::DeviceInfo;
+
+/// Lets the user pick one of the connected devices, either from a given list or by trying regular
+/// expressions against the device names
+#[derive(Debug)]
+pub struct DevicePickerDialog {
+    heading: &'static str,
+    body: &'static str,
+    /// Filter [`Self::candidates`] with a pattern typed in by the user
+    by_pattern: bool,
+    candidates: Vec<DeviceInfo>,
+    /// Devices listed in the dropdown
+    shown: Vec<DeviceInfo>,
+    shown_labels: gtk::StringList,
+    pattern_error: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum DevicePickerMsg {
+    /// Choose one of several connected keyboards
+    ChooseKeyboard(Vec<DeviceInfo>),
+    /// Find one of the devices by a pattern matching its name
+    FindByPattern(Vec<DeviceInfo>),
+    PatternChanged(String),
+    Use,
+}
+
+#[derive(Debug)]
+pub enum DevicePickerOutput {
+    Use(DeviceInfo),
+}
+
+fn device_label(dev: &DeviceInfo) -> String {
+    match &dev.phys {
+        Some(phys) => format!("{} ({phys})", dev.display_name()),
+        None => dev.display_name().to_owned(),
+    }
+}
+
+/// Devices whose name matches `pattern`, or an error if it isn't a valid regular expression.
+/// evremap itself only matches the exact name, so the pattern isn't saved anywhere.
+fn devices_matching(devices: &[DeviceInfo], pattern: &str) -> Result<Vec<DeviceInfo>, String> {
+    let compiled = glib::Regex::new(
+        pattern,
+        glib::RegexCompileFlags::DEFAULT,
+        glib::RegexMatchFlags::DEFAULT,
+    );
+    match compiled {
+        Ok(Some(regex)) if !pattern.is_empty() => Ok(devices
+            .iter()
+            .filter(|d| {
+                let name = glib::GString::from(d.name.as_str());
+                regex
+                    .match_(&name, glib::RegexMatchFlags::DEFAULT)
+                    .is_some()
+            })
+            .cloned()
+            .collect()),
+        Ok(_) => Ok(Vec::new()),
+        Err(e) => Err(e.message().to_owned()),
+    }
+}
+
+impl DevicePickerDialog {
+    fn set_shown(&mut self, devices: Vec<DeviceInfo>) {
+        let labels: Vec<String> = devices.iter().map(device_label).collect();
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        self.shown_labels
+            .splice(0, self.shown_labels.n_items(), &labels);
+        self.shown = devices;
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for DevicePickerDialog {
+    type Init = ();
+    type Input = DevicePickerMsg;
+    type Output = DevicePickerOutput;
+    type CommandOutput = ();
+
+    view! {
+        adw::AlertDialog {
+            #[watch]
+            set_heading: Some(model.heading),
+            #[watch]
+            set_body: model.body,
+            add_responses: &[("cancel", "Cancel"), ("use", "Use")],
+            set_response_appearance: ("use", adw::ResponseAppearance::Suggested),
+            #[watch]
+            set_response_enabled: ("use", !model.shown.is_empty()),
+            set_default_response: Some("use"),
+            set_close_response: "cancel",
+
+            #[wrap(Some)]
+            set_extra_child = &gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 6,
+
+                #[name(pattern_entry)]
+                gtk::Entry {
+                    #[watch]
+                    set_visible: model.by_pattern,
+                    set_placeholder_text: Some("Regular expression, like ^Logitech.*Keyboard"),
+                    connect_changed[sender] => move |entry| {
+                        sender.input(DevicePickerMsg::PatternChanged(entry.text().into()))
+                    },
+                },
+
+                gtk::Label {
+                    add_css_class: "error",
+                    set_wrap: true,
+                    set_xalign: 0.0,
+                    #[watch]
+                    set_visible: model.pattern_error.is_some(),
+                    #[watch]
+                    set_label: model.pattern_error.as_deref().unwrap_or_default(),
+                },
+
+                #[name(dropdown)]
+                gtk::DropDown {
+                    set_model: Some(&model.shown_labels),
+                    #[watch]
+                    set_sensitive: !model.shown.is_empty(),
+                },
+            },
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = Self {
+            heading: "",
+            body: "",
+            by_pattern: false,
+            candidates: Vec::new(),
+            shown: Vec::new(),
+            shown_labels: gtk::StringList::default(),
+            pattern_error: None,
+        };
+        let widgets = view_output!();
+        let use_sender = sender.clone();
+        root.connect_response(Some("use"), move |_, _| {
+            use_sender.input(DevicePickerMsg::Use)
+        });
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        match message {
+            DevicePickerMsg::ChooseKeyboard(keyboards) => {
+                self.heading = "Choose a keyboard";
+                self.body = "Multiple keyboards are connected";
+                self.by_pattern = false;
+                self.pattern_error = None;
+                self.set_shown(keyboards);
+                widgets.dropdown.set_selected(0);
+            }
+            DevicePickerMsg::FindByPattern(devices) => {
+                self.heading = "Find devices by name";
+                self.body = "evremap only matches the exact device name, so the pattern is not \
+                             saved in the config. It helps to pick one of the connected devices \
+                             when the names differ between machines.";
+                self.by_pattern = true;
+                self.candidates = devices;
+                // Don't keep the pattern typed in the last time
+                widgets.pattern_entry.set_text("");
+                self.pattern_error = None;
+                self.set_shown(Vec::new());
+            }
+            DevicePickerMsg::PatternChanged(pattern) => {
+                match devices_matching(&self.candidates, &pattern) {
+                    Ok(found) => {
+                        self.pattern_error = None;
+                        self.set_shown(found);
+                    }
+                    Err(e) => {
+                        self.pattern_error = Some(e);
+                        self.set_shown(Vec::new());
+                    }
+                }
+                widgets.dropdown.set_selected(0);
+            }
+            DevicePickerMsg::Use => {
+                if let Some(dev) = self.shown.get(widgets.dropdown.selected() as usize) {
+                    sender.output(DevicePickerOutput::Use(dev.clone())).unwrap();
+                }
+            }
+        }
+        self.update_view(widgets, sender);
+    }
+}
@@ -1,11 +1,22 @@
+use std::{cell::Cell, collections::HashSet, rc::Rc, time::Duration};
+
+use evdev_rs::enums::EventCode;
 use gtk::prelude::*;
 use relm4::prelude::*;
 
-use super::key_seq::KeySeqInput;
+use super::key_seq::{
+    KeyPresetChange, KeySeqInput, KeySeqInputMsg, KeySeqInputOutput, KeySeqOptions, PasteKeyError,
+};
 use crate::{
-    components::key_seq::new_dropdown_property_expr,
+    components::key_seq::{
+        new_key_dropdown_expr, new_key_dropdown_model, new_key_group_header_factory,
+        new_key_item_factory,
+    },
     config_file::DualRoleConfig,
-    evdev_utils::{KeyCode, list_keycodes, list_keynames_iter},
+    evdev_utils::{
+        KeyCategory, KeyCode, keycode_from_name, list_keycodes, sort_by_group, summarize_keys,
+        unseen_keys_hint,
+    },
 };
 
 #[derive(Debug)]
@@ -13,21 +24,104 @@ pub struct DualRoleMapItem {
     pub key: KeyCode,
     pub hold_seq: Controller<KeySeqInput>,
     pub tap_seq: Controller<KeySeqInput>,
+    /// Keys the config's device supports but that weren't observed from it in the event logger
+    /// this session
+    unseen_keys: Option<HashSet<KeyCode>>,
+    /// Problems were found in the entry when saving
+    save_problem: bool,
+    /// When the trigger key was pressed on the logged device, if it's held now
+    pressed_at: Option<Duration>,
+    /// Another key was pressed while the trigger key was held, which makes it a hold
+    interrupted: bool,
+    /// How the last press of the trigger key would be treated
+    preview: Option<String>,
+    /// Kind of keys listed in the trigger key dropdown
+    trigger_category: KeyCategory,
+    /// Keys listed in the trigger key dropdown, the selected one is always included
+    trigger_keys: Vec<KeyCode>,
+    trigger_names: gtk::StringList,
+    /// The dropdown items are being replaced, selection changes don't come from the user
+    rebuilding_triggers: Rc<Cell<bool>>,
+    /// Read by the item factory of the trigger key dropdown
+    show_key_codes: Rc<Cell<bool>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DualRoleMapItemMsg {
     SelectTriggerKey(KeyCode),
+    SetTriggerCategory(KeyCategory),
+    /// Hold or tap sequence was edited, used to refresh the preview
+    SequenceChanged,
+    SetUnseenKeys(Option<HashSet<KeyCode>>),
+    SetSaveProblem(bool),
+    PasteFailed(PasteKeyError),
+    Preset(KeyPresetChange),
+    /// The presets or the key label format were changed in the settings
+    SetKeySeqOptions(KeySeqOptions),
+    /// A key was pressed or released on the device in the event logger. Like evremap, a press
+    /// of the trigger key is previewed as a tap unless another key is pressed before it's
+    /// released, however long it's held.
+    LoggedKeyEvent {
+        key: KeyCode,
+        pressed: bool,
+        time: Duration,
+    },
 }
 
 #[derive(Debug)]
 pub enum DualRoleMapItemOutput {
     Delete(DynamicIndex),
+    MoveUp(DynamicIndex),
+    MoveDown(DynamicIndex),
+    /// Insert a copy of the entry right after it
+    Duplicate(DynamicIndex),
+    /// Trigger key or one of the sequences was edited
+    Changed,
+    PasteFailed(PasteKeyError),
+    Preset(KeyPresetChange),
+}
+
+impl DualRoleMapItem {
+    /// List the keys of the selected category in the trigger key dropdown
+    fn update_trigger_keys(&mut self) {
+        self.trigger_keys = list_keycodes()
+            .iter()
+            .copied()
+            .filter(|k| self.trigger_category.contains(*k) || *k == self.key)
+            .collect();
+        sort_by_group(&mut self.trigger_keys);
+        let names: Vec<String> = self
+            .trigger_keys
+            .iter()
+            .map(|k| format!("{}", EventCode::EV_KEY(*k)))
+            .collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        self.rebuilding_triggers.set(true);
+        self.trigger_names
+            .splice(0, self.trigger_names.n_items(), &names);
+        self.rebuilding_triggers.set(false);
+    }
+
+    /// Position of the trigger key in the dropdown, it's always listed whatever the category
+    fn trigger_position(&self) -> u32 {
+        self.trigger_keys
+            .iter()
+            .position(|k| *k == self.key)
+            .map_or(gtk::INVALID_LIST_POSITION, |idx| idx as u32)
+    }
+
+    pub fn to_config(&self) -> DualRoleConfig {
+        DualRoleConfig {
+            input: self.key,
+            hold: self.hold_seq.model().sequence.to_keys(),
+            tap: self.tap_seq.model().sequence.to_keys(),
+        }
+    }
 }
 
 #[relm4::factory(pub)]
 impl FactoryComponent for DualRoleMapItem {
-    type Init = DualRoleConfig;
+    type Init = (DualRoleConfig, KeySeqOptions);
     type Input = DualRoleMapItemMsg;
     type Output = DualRoleMapItemOutput;
     type CommandOutput = ();
@@ -36,6 +130,9 @@ impl FactoryComponent for DualRoleMapItem {
     view! {
         #[root]
         gtk::Frame {
+            #[watch]
+            set_class_active: ("warning", self.save_problem),
+
             gtk::Box {
                 set_orientation: gtk::Orientation::Horizontal,
                 set_spacing: 12,
@@ -55,17 +152,43 @@ impl FactoryComponent for DualRoleMapItem {
                         },
 
                         gtk::DropDown::new(
-                            Some(gtk::StringList::from_iter(list_keynames_iter())),
-                            Some(new_dropdown_property_expr())
+                            Some(new_key_dropdown_model(&self.trigger_names)),
+                            Some(new_key_dropdown_expr())
                         ) {
+                            set_factory: Some(&new_key_item_factory(self.show_key_codes.clone())),
                             set_enable_search: true,
                             set_search_match_mode: gtk::StringFilterMatchMode::Substring,
+                            set_header_factory: Some(&new_key_group_header_factory()),
+                            #[watch]
+                            set_selected: self.trigger_position(),
+                            connect_selected_notify[sender, rebuilding = self.rebuilding_triggers.clone()] => move |dd| {
+                                if rebuilding.get() {
+                                    return;
+                                }
+                                if let Some(key) = dd
+                                    .selected_item()
+                                    .and_downcast::<gtk::StringObject>()
+                                    .and_then(|s| keycode_from_name(&s.string()))
+                                {
+                                    sender.input(DualRoleMapItemMsg::SelectTriggerKey(key));
+                                }
+                            }
+                        },
+
+                        gtk::DropDown::from_strings(&KeyCategory::ALL.map(|c| c.label())) {
+                            set_tooltip_text: Some("Kind of keys listed"),
                             connect_selected_notify[sender] => move |dd| {
-                                let idx = dd.selected();
-                                if idx != gtk::INVALID_LIST_POSITION {
-                                    sender.input(DualRoleMapItemMsg::SelectTriggerKey(list_keycodes()[(idx) as usize]));
+                                if let Some(category) = KeyCategory::ALL.get(dd.selected() as usize) {
+                                    sender.input(DualRoleMapItemMsg::SetTriggerCategory(*category));
                                 }
                             }
+                        },
+
+                        gtk::Image::from_icon_name("dialog-information-symbolic") {
+                            #[watch]
+                            set_visible: unseen_keys_hint([self.key], self.unseen_keys.as_ref()).is_some(),
+                            #[watch]
+                            set_tooltip_text: unseen_keys_hint([self.key], self.unseen_keys.as_ref()).as_deref(),
                         }
                     },
 
@@ -88,6 +211,55 @@ impl FactoryComponent for DualRoleMapItem {
 
                         attach[1,2,1,1] = self.tap_seq.widget(),
                     },
+
+                    gtk::Label {
+                        set_halign: gtk::Align::Start,
+                        set_wrap: true,
+                        add_css_class: "dim-label",
+                        #[watch]
+                        set_label: &format!(
+                            "Tap → {}, Hold → {}",
+                            summarize_keys(self.tap_seq.model().sequence.iter()),
+                            summarize_keys(self.hold_seq.model().sequence.iter())
+                        ),
+                    },
+
+                    gtk::Label {
+                        set_halign: gtk::Align::Start,
+                        set_tooltip_text: Some("Press the trigger key on the device in the event logger"),
+                        #[watch]
+                        set_visible: self.preview.is_some(),
+                        #[watch]
+                        set_label: self.preview.as_deref().unwrap_or_default(),
+                    },
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_valign: gtk::Align::Center,
+
+                    gtk::Button::from_icon_name("go-up-symbolic") {
+                        set_has_frame: false,
+                        set_tooltip_text: Some("Move up"),
+                        connect_clicked[sender, index] => move |_| {
+                            sender.output(DualRoleMapItemOutput::MoveUp(index.clone())).unwrap();
+                        }
+                    },
+
+                    gtk::Button::from_icon_name("go-down-symbolic") {
+                        set_has_frame: false,
+                        set_tooltip_text: Some("Move down"),
+                        connect_clicked[sender, index] => move |_| {
+                            sender.output(DualRoleMapItemOutput::MoveDown(index.clone())).unwrap();
+                        }
+                    },
+                },
+
+                gtk::Button::from_icon_name("edit-copy-symbolic") {
+                    set_tooltip_text: Some("Duplicate"),
+                    connect_clicked[sender, index] => move |_| {
+                        sender.output(DualRoleMapItemOutput::Duplicate(index.clone())).unwrap();
+                    }
                 },
 
                 gtk::Button::from_icon_name("edit-delete-symbolic") {
@@ -99,21 +271,104 @@ impl FactoryComponent for DualRoleMapItem {
         }
     }
 
-    fn init_model(init: Self::Init, _index: &Self::Index, _sender: FactorySender<Self>) -> Self {
-        let hold_seq = KeySeqInput::builder().launch(init.hold).detach();
-        let tap_seq = KeySeqInput::builder().launch(init.tap).detach();
-        Self {
+    fn init_model(
+        (init, options): Self::Init,
+        _index: &Self::Index,
+        sender: FactorySender<Self>,
+    ) -> Self {
+        let show_key_codes = Rc::new(Cell::new(options.show_key_codes));
+        let hold_seq = KeySeqInput::builder()
+            .launch((init.hold, options.clone()))
+            .forward(sender.input_sender(), |out| match out {
+                KeySeqInputOutput::Changed => DualRoleMapItemMsg::SequenceChanged,
+                KeySeqInputOutput::PasteFailed(e) => DualRoleMapItemMsg::PasteFailed(e),
+                KeySeqInputOutput::Preset(change) => DualRoleMapItemMsg::Preset(change),
+            });
+        let tap_seq = KeySeqInput::builder().launch((init.tap, options)).forward(
+            sender.input_sender(),
+            |out| match out {
+                KeySeqInputOutput::Changed => DualRoleMapItemMsg::SequenceChanged,
+                KeySeqInputOutput::PasteFailed(e) => DualRoleMapItemMsg::PasteFailed(e),
+                KeySeqInputOutput::Preset(change) => DualRoleMapItemMsg::Preset(change),
+            },
+        );
+        let mut item = Self {
             key: init.input,
             hold_seq,
             tap_seq,
-        }
+            unseen_keys: None,
+            save_problem: false,
+            pressed_at: None,
+            interrupted: false,
+            preview: None,
+            trigger_category: KeyCategory::default(),
+            trigger_keys: Vec::new(),
+            trigger_names: gtk::StringList::default(),
+            rebuilding_triggers: Rc::default(),
+            show_key_codes,
+        };
+        item.update_trigger_keys();
+        item
     }
 
-    fn update(&mut self, message: Self::Input, _sender: FactorySender<Self>) {
+    fn update(&mut self, message: Self::Input, sender: FactorySender<Self>) {
         match message {
             DualRoleMapItemMsg::SelectTriggerKey(k) => {
-                self.key = k;
+                if self.key != k {
+                    self.key = k;
+                    self.save_problem = false;
+                    self.pressed_at = None;
+                    self.preview = None;
+                    sender.output(DualRoleMapItemOutput::Changed).unwrap();
+                }
+            }
+            DualRoleMapItemMsg::SetTriggerCategory(category) => {
+                self.trigger_category = category;
+                self.update_trigger_keys();
+            }
+            DualRoleMapItemMsg::SequenceChanged => {
+                self.save_problem = false;
+                sender.output(DualRoleMapItemOutput::Changed).unwrap()
+            }
+            DualRoleMapItemMsg::SetUnseenKeys(keys) => self.unseen_keys = keys,
+            DualRoleMapItemMsg::SetSaveProblem(problem) => {
+                self.save_problem = problem;
+                self.hold_seq.emit(KeySeqInputMsg::ShowProblems(problem));
+                self.tap_seq.emit(KeySeqInputMsg::ShowProblems(problem));
+            }
+            DualRoleMapItemMsg::LoggedKeyEvent { key, pressed, time } => {
+                if key != self.key {
+                    if pressed && self.pressed_at.is_some() {
+                        self.interrupted = true;
+                    }
+                } else if pressed {
+                    self.pressed_at = Some(time);
+                    self.interrupted = false;
+                    self.preview = Some("Trigger key is held…".to_owned());
+                } else if let Some(pressed_at) = self.pressed_at.take() {
+                    let held = time.saturating_sub(pressed_at);
+                    let kind = if self.interrupted {
+                        "hold, another key was pressed"
+                    } else {
+                        "tap"
+                    };
+                    self.preview = Some(format!("Last press: {kind} ({} ms)", held.as_millis()));
+                }
+            }
+            DualRoleMapItemMsg::SetKeySeqOptions(options) => {
+                if self.show_key_codes.replace(options.show_key_codes) != options.show_key_codes {
+                    self.update_trigger_keys();
+                }
+                self.hold_seq
+                    .emit(KeySeqInputMsg::SetOptions(options.clone()));
+                self.tap_seq.emit(KeySeqInputMsg::SetOptions(options));
             }
+            DualRoleMapItemMsg::PasteFailed(e) => sender
+                .output(DualRoleMapItemOutput::PasteFailed(e))
+                .unwrap(),
+            DualRoleMapItemMsg::Preset(change) => sender
+                .output(DualRoleMapItemOutput::Preset(change))
+                .unwrap(),
         }
     }
 }
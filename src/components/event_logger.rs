@@ -1,16 +1,137 @@
-use std::{error::Error, sync::mpsc};
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs::OpenOptions,
+    io::ErrorKind,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
 
 use evdev_rs::enums::EventCode;
 use gtk::prelude::*;
 use relm4::{Sender, prelude::*};
+use relm4_components::save_dialog::{
+    SaveDialog, SaveDialogMsg, SaveDialogResponse, SaveDialogSettings,
+};
+
+use crate::{
+    components::device_browser::copy_device_field,
+    deviceinfo::{DeviceField, DeviceId, DeviceInfo},
+    evdev_utils::{KeyCode, key_label, keycode_from_name},
+};
+
+/// Captured key presses beyond this are dropped starting from the oldest
+const MAX_CAPTURED_KEYS: usize = 8;
+/// How long the logger thread sleeps when the device has no events, this is also how long it
+/// takes for the thread to notice a stop request
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Default for how many lines the log keeps before dropping the oldest ones
+pub const DEFAULT_MAX_LOG_LINES: u32 = 5000;
+
+/// Kinds of events that can be shown in the log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventCategory {
+    Key,
+    Relative,
+    Absolute,
+    Switch,
+}
+
+impl EventCategory {
+    const ALL: [Self; 4] = [Self::Key, Self::Relative, Self::Absolute, Self::Switch];
+
+    fn of(code: &EventCode) -> Option<Self> {
+        match code {
+            EventCode::EV_KEY(_) => Some(Self::Key),
+            EventCode::EV_REL(_) => Some(Self::Relative),
+            EventCode::EV_ABS(_) => Some(Self::Absolute),
+            EventCode::EV_SW(_) => Some(Self::Switch),
+            _ => None,
+        }
+    }
 
-use crate::{deviceinfo::DeviceInfo, evdev_utils::KeyCode};
+    fn label(self) -> &'static str {
+        match self {
+            Self::Key => "Keys",
+            Self::Relative => "Relative axes",
+            Self::Absolute => "Absolute axes",
+            Self::Switch => "Switches",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct HeldKeyChip {
+    key: KeyCode,
+    show_code: bool,
+}
+
+#[relm4::factory]
+impl FactoryComponent for HeldKeyChip {
+    type Init = (KeyCode, bool);
+    type Input = ();
+    type Output = ();
+    type ParentWidget = gtk::Box;
+    type CommandOutput = ();
+
+    view! {
+        #[root]
+        gtk::Label {
+            set_label: &key_label(self.key, self.show_code),
+            add_css_class: "card",
+            set_margin_all: 2,
+        }
+    }
+
+    fn init_model(
+        (key, show_code): Self::Init,
+        _index: &Self::Index,
+        _sender: FactorySender<Self>,
+    ) -> Self {
+        Self { key, show_code }
+    }
+}
 
 #[derive(Debug)]
 pub struct EventLogger {
     device: Option<DeviceLoggerState>,
     text_buf: gtk::TextBuffer,
     is_paused: bool,
+    /// Merge consecutive identical events into a single line with a counter
+    collapse_repeats: bool,
+    /// Text of the last logged line and how many times in a row it was logged
+    last_line: Option<(String, usize)>,
+    /// Start of the last logged line in [`Self::text_buf`]
+    last_line_mark: gtk::TextMark,
+    /// The oldest lines are removed from the log past this count
+    max_lines: u32,
+    /// Keep the log scrolled to the newest line
+    autoscroll: bool,
+    /// Add the numeric codes to key names, see [`key_label`]
+    show_key_codes: bool,
+    /// Always at the end of [`Self::text_buf`], used as the autoscroll target
+    end_mark: gtk::TextMark,
+    /// Keys of the logged device that are currently pressed down
+    held_keys: HashSet<KeyCode>,
+    /// Keys that were pressed at least once since the device was selected
+    observed_keys: HashSet<KeyCode>,
+    held_keys_factory: FactoryVecDeque<HeldKeyChip>,
+    /// Last logged key presses, in the order they were pressed
+    captured_keys: Vec<KeyCode>,
+    /// Check buttons of the observed keys to create one remap each for
+    key_picker: gtk::Box,
+    key_picker_checks: Vec<(KeyCode, gtk::CheckButton)>,
+    /// Only events of these keys are logged, all of them if empty
+    key_filter: HashSet<KeyCode>,
+    /// Names in the filter that don't match any key
+    unknown_filter_names: Vec<String>,
+    /// Event categories that are logged
+    shown_categories: HashSet<EventCategory>,
+    export_dialog: Controller<SaveDialog>,
+    /// Incremented every time a device is selected, see [`DeviceLoggerState::session`]
+    last_session: u32,
 }
 
 #[derive(Debug)]
@@ -22,6 +143,17 @@ enum BgTaskMsg {
 struct DeviceLoggerState {
     device: DeviceInfo,
     bg_task_sender: mpsc::Sender<BgTaskMsg>,
+    /// Tags the messages of the logging task. A task that was told to stop may still send some
+    /// before it notices, and those must not be mistaken for events of the current device.
+    session: u32,
+}
+
+/// Stops the logging task when the device is replaced or cleared, or the component is dropped
+impl Drop for DeviceLoggerState {
+    fn drop(&mut self) {
+        // The task may have already exited because of an error
+        let _ = self.bg_task_sender.send(BgTaskMsg::Stop);
+    }
 }
 
 #[derive(Debug)]
@@ -29,19 +161,79 @@ pub enum EventLoggerMsg {
     Pause,
     Resume,
     Clear,
+    SetCollapseRepeats(bool),
+    SetMaxLines(u32),
+    /// The key label format was changed in the settings, applies to new lines
+    SetShowKeyCodes(bool),
+    SetAutoscroll(bool),
+    /// Comma-separated names of the keys to log
+    SetKeyFilter(String),
+    SetCategoryShown(EventCategory, bool),
+    /// Send the captured key presses out to be used as a remap input
+    UseCaptured,
+    ClearCaptured,
+    /// List the observed keys in the key picker
+    FillKeyPicker,
+    /// Send the keys checked in the key picker out to get a remap each
+    RemapPickedKeys,
+    /// Ask where to save the log
+    ExportRequest,
+    /// User has selected a file to export the log to
+    ExportResponse(PathBuf),
+    /// Message to trigger a redraw, completely ignored otherwise
+    Ignore,
     SetDevice(DeviceInfo),
     ClearDevice,
+    /// Stop logging like [`EventLoggerMsg::ClearDevice`], but without reporting the device as
+    /// removed by the user, so it's still opened on the next start
+    StopLogging,
+    CopyDeviceField(DeviceField),
 }
 
+/// Messages of the logging task, each with the [`DeviceLoggerState::session`] it belongs to
 #[derive(Debug)]
 pub enum EventCommandMsg {
-    NewEvent(KeyCode, i32),
-    ErrorOccured(std::io::Error),
+    /// Event code, value and the kernel timestamp of the event
+    NewEvent(u32, EventCode, i32, Duration),
+    /// Events were dropped by the kernel, the device state is about to be resent
+    Resync(u32),
+    ErrorOccured(u32, std::io::Error),
+}
+
+impl EventCommandMsg {
+    fn session(&self) -> u32 {
+        match self {
+            EventCommandMsg::NewEvent(session, ..)
+            | EventCommandMsg::Resync(session)
+            | EventCommandMsg::ErrorOccured(session, _) => *session,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum EventLoggerOutput {
     ErrorOccured(Box<dyn Error + Send + 'static>, Option<String>),
+    /// Logging device was removed by the user
+    DeviceCleared,
+    /// A key was pressed on the logged device for the first time
+    KeyObserved(DeviceId, KeyCode),
+    /// User wants a new remap with the captured key presses as the input
+    CaptureSequence(Vec<KeyCode>),
+    /// User wants a new remap for each of the keys, with just that key as the input
+    RemapEachKey(Vec<KeyCode>),
+    /// The log was written to a file
+    LogExported(PathBuf),
+    /// A key was pressed or released on the logged device, `time` is the kernel timestamp
+    KeyEvent {
+        device: DeviceId,
+        key: KeyCode,
+        pressed: bool,
+        time: Duration,
+    },
+    /// A field of the logged device was put on the clipboard
+    Copied(DeviceField),
+    /// The log line limit was changed, to be remembered in the settings
+    MaxLinesChanged(u32),
 }
 
 #[relm4::component(pub)]
@@ -83,12 +275,49 @@ impl Component for EventLogger {
                     connect_clicked => EventLoggerMsg::Clear,
                 },
 
+                gtk::Button::from_icon_name("document-save-as-symbolic") {
+                    set_tooltip_text: Some("Export log"),
+                    #[watch]
+                    set_sensitive: model.device.is_some(),
+                    connect_clicked => EventLoggerMsg::ExportRequest,
+                },
+
                 gtk::Button::from_icon_name("edit-delete-symbolic") {
                     set_tooltip_text: Some("Clear device"),
                     #[watch]
                     set_sensitive: model.device.is_some(),
                     connect_clicked => EventLoggerMsg::ClearDevice,
-                }
+                },
+
+                gtk::CheckButton::with_label("Merge repeated events") {
+                    set_active: model.collapse_repeats,
+                    connect_toggled[sender] => move |cb| {
+                        sender.input(EventLoggerMsg::SetCollapseRepeats(cb.is_active()))
+                    }
+                },
+
+                gtk::ToggleButton::with_label("Autoscroll") {
+                    set_tooltip_text: Some("Follow the newest events"),
+                    set_active: model.autoscroll,
+                    connect_toggled[sender] => move |tb| {
+                        sender.input(EventLoggerMsg::SetAutoscroll(tb.is_active()))
+                    }
+                },
+
+                gtk::Label {
+                    set_label: "Max lines:",
+                    set_hexpand: true,
+                    set_halign: gtk::Align::End,
+                },
+
+                gtk::SpinButton::with_range(100.0, 100_000.0, 100.0) {
+                    set_tooltip_text: Some("The oldest lines are removed from the log past this count"),
+                    #[watch]
+                    set_value: model.max_lines as f64,
+                    connect_value_changed[sender] => move |sb| {
+                        sender.input(EventLoggerMsg::SetMaxLines(sb.value_as_int() as u32))
+                    }
+                },
             },
 
             gtk::Frame {
@@ -109,13 +338,22 @@ impl Component for EventLogger {
 
                             attach[1,0,1,1] = &gtk::Label {
                                 #[watch]
-                                set_label: &dev.device.name,
+                                set_label: &match &dev.device.alias {
+                                    Some(alias) => format!("{alias} ({})", dev.device.name),
+                                    None => dev.device.name.clone(),
+                                },
                                 set_selectable: true,
                                 set_halign: gtk::Align::Start,
                                 set_hexpand: true,
                                 set_ellipsize: gtk::pango::EllipsizeMode::End
                             },
 
+                            attach[2,0,1,1] = &gtk::Button::from_icon_name("edit-copy-symbolic") {
+                                set_tooltip_text: Some("Copy name"),
+                                set_has_frame: false,
+                                connect_clicked => EventLoggerMsg::CopyDeviceField(DeviceField::Name),
+                            },
+
                             attach[0,1,1,1] = &gtk::Label {
                                 set_label: "Device phys:",
                                 set_halign: gtk::Align::Start,
@@ -131,6 +369,14 @@ impl Component for EventLogger {
                                 set_ellipsize: gtk::pango::EllipsizeMode::End
                             },
 
+                            attach[2,1,1,1] = &gtk::Button::from_icon_name("edit-copy-symbolic") {
+                                set_tooltip_text: Some("Copy phys"),
+                                set_has_frame: false,
+                                #[watch]
+                                set_sensitive: dev.device.phys.is_some(),
+                                connect_clicked => EventLoggerMsg::CopyDeviceField(DeviceField::Phys),
+                            },
+
                             attach[0,2,1,1] = &gtk::Label {
                                 set_label: "Device path:",
                                 set_halign: gtk::Align::Start,
@@ -145,6 +391,12 @@ impl Component for EventLogger {
                                 set_hexpand: true,
                                 set_ellipsize: gtk::pango::EllipsizeMode::End
                             },
+
+                            attach[2,2,1,1] = &gtk::Button::from_icon_name("edit-copy-symbolic") {
+                                set_tooltip_text: Some("Copy path"),
+                                set_has_frame: false,
+                                connect_clicked => EventLoggerMsg::CopyDeviceField(DeviceField::Path),
+                            },
                         }
                     },
                     None => {
@@ -155,10 +407,134 @@ impl Component for EventLogger {
                 }
             },
 
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 6,
+                #[watch]
+                set_visible: model.device.is_some(),
+
+                gtk::Label {
+                    set_label: "Held keys:",
+                },
+
+                gtk::Label {
+                    set_label: "(None)",
+                    #[watch]
+                    set_visible: model.held_keys.is_empty(),
+                },
+
+                gtk::ScrolledWindow {
+                    set_hexpand: true,
+                    set_policy: (gtk::PolicyType::Automatic, gtk::PolicyType::Never),
+
+                    #[local_ref]
+                    held_keys_box -> gtk::Box {
+                        set_spacing: 6,
+                    },
+                },
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 6,
+                #[watch]
+                set_visible: model.device.is_some(),
+
+                gtk::Label {
+                    set_label: "Captured keys:",
+                },
+
+                gtk::Label {
+                    set_hexpand: true,
+                    set_halign: gtk::Align::Start,
+                    set_ellipsize: gtk::pango::EllipsizeMode::Start,
+                    #[watch]
+                    set_label: &model.captured_keys_label(),
+                },
+
+                gtk::Button::with_label("Use in new remap") {
+                    set_tooltip_text: Some(&format!(
+                        "Create a remap with the last {MAX_CAPTURED_KEYS} pressed keys as the input"
+                    )),
+                    #[watch]
+                    set_sensitive: !model.captured_keys.is_empty(),
+                    connect_clicked => EventLoggerMsg::UseCaptured,
+                },
+
+                gtk::MenuButton {
+                    set_label: "Remap each key…",
+                    set_tooltip_text: Some("Create a remap for each of the picked keys pressed on the device"),
+                    #[watch]
+                    set_sensitive: !model.observed_keys.is_empty(),
+                    #[wrap(Some)]
+                    set_popover = &gtk::Popover {
+                        connect_show => EventLoggerMsg::FillKeyPicker,
+
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+                            set_spacing: 6,
+
+                            gtk::ScrolledWindow {
+                                set_hscrollbar_policy: gtk::PolicyType::Never,
+                                set_propagate_natural_height: true,
+                                set_max_content_height: 300,
+                                set_child: Some(&model.key_picker),
+                            },
+
+                            gtk::Button::with_label("Add Remaps") {
+                                add_css_class: "suggested-action",
+                                connect_clicked[sender] => move |btn| {
+                                    sender.input(EventLoggerMsg::RemapPickedKeys);
+                                    if let Some(popover) = btn
+                                        .ancestor(gtk::Popover::static_type())
+                                        .and_downcast::<gtk::Popover>()
+                                    {
+                                        popover.popdown();
+                                    }
+                                },
+                            },
+                        },
+                    },
+                },
+
+                gtk::Button::from_icon_name("edit-clear-symbolic") {
+                    set_tooltip_text: Some("Clear captured keys"),
+                    #[watch]
+                    set_sensitive: !model.captured_keys.is_empty(),
+                    connect_clicked => EventLoggerMsg::ClearCaptured,
+                },
+            },
+
+            #[name(categories_box)]
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 6,
+
+                gtk::Label {
+                    set_label: "Show:",
+                },
+            },
+
+            gtk::Entry {
+                set_placeholder_text: Some("Only show keys, e.g. a, leftctrl, KEY_ESC"),
+                set_primary_icon_name: Some("system-search-symbolic"),
+                #[watch]
+                set_class_active: ("error", !model.unknown_filter_names.is_empty()),
+                #[watch]
+                set_tooltip_text: (!model.unknown_filter_names.is_empty())
+                    .then(|| format!("Unknown keys: {}", model.unknown_filter_names.join(", ")))
+                    .as_deref(),
+                connect_changed[sender] => move |entry| {
+                    sender.input(EventLoggerMsg::SetKeyFilter(entry.text().to_string()))
+                }
+            },
+
             gtk::ScrolledWindow {
                 set_vexpand: true,
+                #[name(log_view)]
                 gtk::TextView {
                     set_editable: false,
+                    set_monospace: true,
                     set_vscroll_policy: gtk::ScrollablePolicy::Minimum,
                     set_buffer: Some(&model.text_buf)
                 }
@@ -171,18 +547,55 @@ impl Component for EventLogger {
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
+        let text_buf = gtk::TextBuffer::default();
+        let last_line_mark = text_buf.create_mark(None, &text_buf.end_iter(), true);
+        let end_mark = text_buf.create_mark(None, &text_buf.end_iter(), false);
         let mut model = Self {
             device: None,
-            text_buf: gtk::TextBuffer::default(),
+            text_buf,
             is_paused: true,
+            collapse_repeats: false,
+            last_line: None,
+            last_line_mark,
+            max_lines: DEFAULT_MAX_LOG_LINES,
+            autoscroll: true,
+            show_key_codes: true,
+            end_mark,
+            held_keys: HashSet::new(),
+            observed_keys: HashSet::new(),
+            held_keys_factory: FactoryVecDeque::builder().launch_default().detach(),
+            captured_keys: Vec::new(),
+            key_picker: gtk::Box::new(gtk::Orientation::Vertical, 0),
+            key_picker_checks: Vec::new(),
+            key_filter: HashSet::new(),
+            unknown_filter_names: Vec::new(),
+            shown_categories: HashSet::from([EventCategory::Key]),
+            last_session: 0,
+            export_dialog: SaveDialog::builder()
+                .launch(SaveDialogSettings::default())
+                .forward(sender.input_sender(), |response| match response {
+                    SaveDialogResponse::Cancel => EventLoggerMsg::Ignore,
+                    SaveDialogResponse::Accept(path) => EventLoggerMsg::ExportResponse(path),
+                }),
         };
 
         if let Some(dev) = init {
             model.set_device(dev, sender.clone());
         }
 
+        let held_keys_box = model.held_keys_factory.widget();
         let widgets = view_output!();
 
+        for category in EventCategory::ALL {
+            let check = gtk::CheckButton::with_label(category.label());
+            check.set_active(model.shown_categories.contains(&category));
+            let sender = sender.clone();
+            check.connect_toggled(move |cb| {
+                sender.input(EventLoggerMsg::SetCategoryShown(category, cb.is_active()))
+            });
+            widgets.categories_box.append(&check);
+        }
+
         ComponentParts { model, widgets }
     }
 
@@ -192,28 +605,162 @@ impl Component for EventLogger {
             EventLoggerMsg::Resume => self.is_paused = false,
             EventLoggerMsg::Clear => {
                 self.is_paused = true;
-                self.text_buf.set_text("")
+                self.clear_log();
+            }
+            EventLoggerMsg::SetCollapseRepeats(collapse) => {
+                self.collapse_repeats = collapse;
+                self.last_line = None;
             }
+            EventLoggerMsg::SetAutoscroll(autoscroll) => self.autoscroll = autoscroll,
+            EventLoggerMsg::SetShowKeyCodes(show) => {
+                self.show_key_codes = show;
+                self.held_keys_factory_update();
+            }
+            EventLoggerMsg::SetMaxLines(max_lines) => {
+                if self.max_lines != max_lines {
+                    self.max_lines = max_lines;
+                    self.trim_log();
+                    sender
+                        .output(EventLoggerOutput::MaxLinesChanged(max_lines))
+                        .unwrap();
+                }
+            }
+            EventLoggerMsg::SetKeyFilter(text) => self.set_key_filter(&text),
+            EventLoggerMsg::SetCategoryShown(category, shown) => {
+                if shown {
+                    self.shown_categories.insert(category);
+                } else {
+                    self.shown_categories.remove(&category);
+                }
+            }
+            EventLoggerMsg::UseCaptured => {
+                if !self.captured_keys.is_empty() {
+                    let keys = std::mem::take(&mut self.captured_keys);
+                    sender
+                        .output(EventLoggerOutput::CaptureSequence(keys))
+                        .unwrap();
+                }
+            }
+            EventLoggerMsg::ClearCaptured => self.captured_keys.clear(),
+            EventLoggerMsg::FillKeyPicker => self.fill_key_picker(),
+            EventLoggerMsg::RemapPickedKeys => {
+                let keys: Vec<KeyCode> = self
+                    .key_picker_checks
+                    .iter()
+                    .filter(|(_, check)| check.is_active())
+                    .map(|(key, _)| *key)
+                    .collect();
+                if !keys.is_empty() {
+                    sender
+                        .output(EventLoggerOutput::RemapEachKey(keys))
+                        .unwrap();
+                }
+            }
+            EventLoggerMsg::ExportRequest => self
+                .export_dialog
+                .emit(SaveDialogMsg::SaveAs("events.log".to_owned())),
+            EventLoggerMsg::ExportResponse(path) => match self.export_log(&path) {
+                Ok(()) => sender.output(EventLoggerOutput::LogExported(path)).unwrap(),
+                Err(e) => sender
+                    .output(EventLoggerOutput::ErrorOccured(
+                        Box::new(e),
+                        Some("Failed to export the event log".to_owned()),
+                    ))
+                    .unwrap(),
+            },
+            EventLoggerMsg::Ignore => {}
             EventLoggerMsg::SetDevice(dev) => self.set_device(dev, sender),
-            EventLoggerMsg::ClearDevice => self.clear_device(),
+            EventLoggerMsg::ClearDevice => {
+                self.clear_device();
+                sender.output(EventLoggerOutput::DeviceCleared).unwrap();
+            }
+            EventLoggerMsg::StopLogging => self.clear_device(),
+            EventLoggerMsg::CopyDeviceField(field) => {
+                if let Some(dev) = &self.device
+                    && copy_device_field(&dev.device, field)
+                {
+                    sender.output(EventLoggerOutput::Copied(field)).unwrap();
+                }
+            }
         }
     }
 
+    fn update_cmd_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::CommandOutput,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        self.update_cmd(message, sender.clone(), root);
+        if self.autoscroll {
+            widgets
+                .log_view
+                .scroll_to_mark(&self.end_mark, 0.0, false, 0.0, 1.0);
+        }
+        self.update_view(widgets, sender);
+    }
+
     fn update_cmd(
         &mut self,
         message: Self::CommandOutput,
         sender: ComponentSender<Self>,
         _root: &Self::Root,
     ) {
+        // Left over from a device that is no longer logged
+        if self.device.as_ref().map(|dev_state| dev_state.session) != Some(message.session()) {
+            return;
+        }
         match message {
-            EventCommandMsg::NewEvent(key, val) => {
-                if !self.is_paused && self.device.is_some() {
-                    let new_text = format!("{} {val}\n", EventCode::EV_KEY(key));
-                    let mut end_iter = self.text_buf.end_iter();
-                    self.text_buf.insert(&mut end_iter, &new_text);
+            EventCommandMsg::NewEvent(_, EventCode::EV_KEY(key), val, time) => {
+                self.update_held_keys(key, val);
+                // Repeats don't matter for the tap and hold preview
+                if let Some(dev_state) = &self.device
+                    && val != 2
+                {
+                    sender
+                        .output(EventLoggerOutput::KeyEvent {
+                            device: DeviceId::of(&dev_state.device),
+                            key,
+                            pressed: val == 1,
+                            time,
+                        })
+                        .unwrap();
+                }
+                if let Some(dev_state) = &self.device
+                    && val == 1
+                    && self.observed_keys.insert(key)
+                {
+                    sender
+                        .output(EventLoggerOutput::KeyObserved(
+                            DeviceId::of(&dev_state.device),
+                            key,
+                        ))
+                        .unwrap();
                 }
+                if !self.is_paused {
+                    if val == 1 {
+                        self.capture_key(key);
+                    }
+                    if self.shown_categories.contains(&EventCategory::Key)
+                        && (self.key_filter.is_empty() || self.key_filter.contains(&key))
+                    {
+                        self.log_event(&EventCode::EV_KEY(key), val);
+                    }
+                }
+            }
+            EventCommandMsg::NewEvent(_, code, val, _) => {
+                let shown = EventCategory::of(&code)
+                    .is_some_and(|category| self.shown_categories.contains(&category));
+                if shown && !self.is_paused {
+                    self.log_event(&code, val);
+                }
+            }
+            EventCommandMsg::Resync(_) => {
+                self.held_keys.clear();
+                self.held_keys_factory_update();
             }
-            EventCommandMsg::ErrorOccured(e) => sender
+            EventCommandMsg::ErrorOccured(_, e) => sender
                 .output(EventLoggerOutput::ErrorOccured(
                     Box::new(e),
                     Some("Event logger error".to_owned()),
@@ -223,13 +770,38 @@ impl Component for EventLogger {
     }
 }
 
+/// Kernel timestamp of an event, only meaningful compared to timestamps of other events
+fn event_time(time: &evdev_rs::TimeVal) -> Duration {
+    Duration::from_secs(time.tv_sec.max(0) as u64)
+        + Duration::from_micros(time.tv_usec.max(0) as u64)
+}
+
+/// Describe the value of an event the way evdev defines it for the event type
+fn event_value_name(code: &EventCode, val: i32) -> String {
+    match (code, val) {
+        (EventCode::EV_KEY(_), 0) => "released".to_owned(),
+        (EventCode::EV_KEY(_), 1) => "pressed".to_owned(),
+        (EventCode::EV_KEY(_), 2) => "repeat".to_owned(),
+        (EventCode::EV_SW(_), 0) => "off".to_owned(),
+        (EventCode::EV_SW(_), 1) => "on".to_owned(),
+        (_, other) => other.to_string(),
+    }
+}
+
 impl EventLogger {
+    /// Read events from the device until a stop is requested or the sender of `bg_recv` is
+    /// dropped together with the component. Reads don't block, so the stop request is noticed
+    /// even if the device is idle.
     fn event_logger_task(
         cmd_sender: Sender<EventCommandMsg>,
         dev: DeviceInfo,
+        session: u32,
         bg_recv: mpsc::Receiver<BgTaskMsg>,
     ) -> std::io::Result<()> {
-        let dev_f = std::fs::File::open(&dev.path)?;
+        let dev_f = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&dev.path)?;
         let input_dev = evdev_rs::Device::new_from_file(dev_f)?;
 
         loop {
@@ -238,43 +810,214 @@ impl EventLogger {
                 Err(mpsc::TryRecvError::Disconnected) => break,
                 _ => {}
             }
-            let (status, event) =
-                input_dev.next_event(evdev_rs::ReadFlag::NORMAL | evdev_rs::ReadFlag::BLOCKING)?;
+            let (status, event) = match input_dev.next_event(evdev_rs::ReadFlag::NORMAL) {
+                Ok(res) => res,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(EVENT_POLL_INTERVAL);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let mut events = Vec::new();
             match status {
-                evdev_rs::ReadStatus::Success => {
-                    if let EventCode::EV_KEY(key) = event.event_code {
-                        cmd_sender
-                            .send(EventCommandMsg::NewEvent(key, event.value))
-                            .unwrap();
+                evdev_rs::ReadStatus::Success => events.push(event),
+                evdev_rs::ReadStatus::Sync => {
+                    if cmd_sender.send(EventCommandMsg::Resync(session)).is_err() {
+                        break;
+                    }
+                    // Replay the current device state, libevdev signals the end with EAGAIN
+                    while let Ok((evdev_rs::ReadStatus::Sync, event)) =
+                        input_dev.next_event(evdev_rs::ReadFlag::SYNC)
+                    {
+                        events.push(event);
                     }
                 }
-                evdev_rs::ReadStatus::Sync => break,
+            }
+            for event in events {
+                if EventCategory::of(&event.event_code).is_some()
+                    && cmd_sender
+                        .send(EventCommandMsg::NewEvent(
+                            session,
+                            event.event_code,
+                            event.value,
+                            event_time(&event.time),
+                        ))
+                        .is_err()
+                {
+                    // The component is gone
+                    return Ok(());
+                }
             }
         }
         Ok(())
     }
 
+    fn log_event(&mut self, code: &EventCode, val: i32) {
+        let name = match code {
+            EventCode::EV_KEY(key) => key_label(*key, self.show_key_codes),
+            _ => code.to_string(),
+        };
+        self.log_line(format!("{name:<24} {}", event_value_name(code, val)));
+    }
+
+    /// Append a line to the log, or bump the counter of the last line if it's the same event
+    fn log_line(&mut self, line: String) {
+        match &mut self.last_line {
+            Some((last, count)) if self.collapse_repeats && *last == line => {
+                *count += 1;
+                let mut start = self.text_buf.iter_at_mark(&self.last_line_mark);
+                let mut end = self.text_buf.end_iter();
+                self.text_buf.delete(&mut start, &mut end);
+                self.text_buf
+                    .insert(&mut start, &format!("{line} (x{count})\n"));
+            }
+            _ => {
+                let mut end_iter = self.text_buf.end_iter();
+                self.text_buf.move_mark(&self.last_line_mark, &end_iter);
+                self.text_buf.insert(&mut end_iter, &format!("{line}\n"));
+                self.last_line = Some((line, 1));
+                self.trim_log();
+            }
+        }
+    }
+
+    /// Remove the oldest lines that don't fit into [`Self::max_lines`]
+    fn trim_log(&mut self) {
+        // The buffer ends with a newline, which makes an extra empty line
+        let line_count = self.text_buf.line_count() - 1;
+        let excess = line_count - self.max_lines as i32;
+        if excess > 0 {
+            let mut start = self.text_buf.start_iter();
+            let mut end = self
+                .text_buf
+                .iter_at_line(excess)
+                .unwrap_or_else(|| self.text_buf.end_iter());
+            self.text_buf.delete(&mut start, &mut end);
+        }
+    }
+
+    /// Parse the comma-separated key names, unknown names are remembered to be shown to the user
+    fn set_key_filter(&mut self, text: &str) {
+        self.key_filter.clear();
+        self.unknown_filter_names.clear();
+        for name in text.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            match keycode_from_name(name) {
+                Some(key) => {
+                    self.key_filter.insert(key);
+                }
+                None => self.unknown_filter_names.push(name.to_owned()),
+            }
+        }
+    }
+
+    fn capture_key(&mut self, key: KeyCode) {
+        if self.captured_keys.len() >= MAX_CAPTURED_KEYS {
+            self.captured_keys.remove(0);
+        }
+        self.captured_keys.push(key);
+    }
+
+    /// Replace the key picker contents with the observed keys, none of them checked
+    fn fill_key_picker(&mut self) {
+        while let Some(child) = self.key_picker.first_child() {
+            self.key_picker.remove(&child);
+        }
+        let mut keys: Vec<KeyCode> = self.observed_keys.iter().copied().collect();
+        keys.sort_by_key(|k| *k as u32);
+        self.key_picker_checks = keys
+            .into_iter()
+            .map(|key| {
+                let check = gtk::CheckButton::with_label(&EventCode::EV_KEY(key).to_string());
+                self.key_picker.append(&check);
+                (key, check)
+            })
+            .collect();
+    }
+
+    fn captured_keys_label(&self) -> String {
+        if self.captured_keys.is_empty() {
+            return "(None)".to_owned();
+        }
+        self.captured_keys
+            .iter()
+            .map(|k| EventCode::EV_KEY(*k).to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Write the log to a file, preceded by a comment block describing the device
+    fn export_log(&self, path: &Path) -> std::io::Result<()> {
+        let mut contents = String::new();
+        if let Some(dev_state) = &self.device {
+            let dev = &dev_state.device;
+            contents.push_str(&format!("# Device name: {}\n", dev.name));
+            contents.push_str(&format!(
+                "# Device phys: {}\n",
+                dev.phys.as_deref().unwrap_or("(Missing)")
+            ));
+            contents.push_str(&format!("# Device path: {}\n\n", dev.path.display()));
+        }
+        let (start, end) = self.text_buf.bounds();
+        contents.push_str(&self.text_buf.text(&start, &end, false));
+        std::fs::write(path, contents)
+    }
+
+    fn clear_log(&mut self) {
+        self.text_buf.set_text("");
+        self.last_line = None;
+        self.captured_keys.clear();
+    }
+
+    /// Track key presses and releases, key repeats don't change the held state
+    fn update_held_keys(&mut self, key: KeyCode, val: i32) {
+        let changed = match val {
+            0 => self.held_keys.remove(&key),
+            1 => self.held_keys.insert(key),
+            _ => false,
+        };
+        if changed {
+            self.held_keys_factory_update();
+        }
+    }
+
+    fn held_keys_factory_update(&mut self) {
+        let mut keys: Vec<KeyCode> = self.held_keys.iter().copied().collect();
+        keys.sort_by_key(|k| *k as u32);
+        let mut chips = self.held_keys_factory.guard();
+        chips.clear();
+        for key in keys {
+            chips.push_back((key, self.show_key_codes));
+        }
+    }
+
     fn set_device(&mut self, dev: DeviceInfo, sender: ComponentSender<Self>) {
         self.is_paused = true;
-        self.text_buf.set_text("");
+        self.clear_log();
+        self.observed_keys.clear();
+        self.held_keys.clear();
+        self.held_keys_factory_update();
         let (bg_sender, bg_recv) = mpsc::channel();
+        self.last_session = self.last_session.wrapping_add(1);
+        let session = self.last_session;
         self.device = Some(DeviceLoggerState {
             device: dev.clone(),
             bg_task_sender: bg_sender,
+            session,
         });
         sender.spawn_command(move |cmd_sender| {
-            let res = Self::event_logger_task(cmd_sender.clone(), dev, bg_recv);
+            let res = Self::event_logger_task(cmd_sender.clone(), dev, session, bg_recv);
             if let Err(e) = res {
-                let _ = cmd_sender.send(EventCommandMsg::ErrorOccured(e));
+                let _ = cmd_sender.send(EventCommandMsg::ErrorOccured(session, e));
             }
         });
     }
 
     fn clear_device(&mut self) {
         self.is_paused = true;
-        self.text_buf.set_text("");
-        if let Some(dev_state) = self.device.take() {
-            dev_state.bg_task_sender.send(BgTaskMsg::Stop).unwrap();
-        }
+        self.clear_log();
+        self.held_keys.clear();
+        self.held_keys_factory_update();
+        // Stops the logging task
+        self.device = None;
     }
 }
@@ -1,25 +1,86 @@
-use evdev_rs::enums::EventCode;
-use gtk::prelude::*;
+use std::{cell::Cell, rc::Rc};
+
+use evdev_rs::enums::{EventCode, int_to_ev_key};
+use gtk::{gdk, prelude::*};
 use relm4::prelude::*;
+use thiserror::Error;
 
 use crate::{
-    evdev_utils::{KeyCode, list_keycodes, list_keynames_iter},
-    key_combo::KeyCombination,
+    evdev_utils::{
+        KeyCategory, KeyCode, KeyGroup, key_from_hardware_keycode, key_label, keycode_from_name,
+        list_keycodes, parse_key_list, sort_by_group, summarize_keys,
+    },
+    key_combo::{KeyCombination, is_modifier},
+    settings::KeyPreset,
 };
 
+/// Presets offered in every sequence input in addition to the user's ones
+const BUILTIN_PRESETS: &[(&str, &[KeyCode])] = &[
+    (
+        "Ctrl+Alt+Delete",
+        &[
+            KeyCode::KEY_LEFTCTRL,
+            KeyCode::KEY_LEFTALT,
+            KeyCode::KEY_DELETE,
+        ],
+    ),
+    (
+        "Ctrl+Shift+Escape",
+        &[
+            KeyCode::KEY_LEFTCTRL,
+            KeyCode::KEY_LEFTSHIFT,
+            KeyCode::KEY_ESC,
+        ],
+    ),
+    ("Ctrl+C", &[KeyCode::KEY_LEFTCTRL, KeyCode::KEY_C]),
+    ("Ctrl+V", &[KeyCode::KEY_LEFTCTRL, KeyCode::KEY_V]),
+    ("Ctrl+Z", &[KeyCode::KEY_LEFTCTRL, KeyCode::KEY_Z]),
+    ("Super+L", &[KeyCode::KEY_LEFTMETA, KeyCode::KEY_L]),
+    ("Play/Pause", &[KeyCode::KEY_PLAYPAUSE]),
+    ("Next track", &[KeyCode::KEY_NEXTSONG]),
+    ("Previous track", &[KeyCode::KEY_PREVIOUSSONG]),
+    ("Volume up", &[KeyCode::KEY_VOLUMEUP]),
+    ("Volume down", &[KeyCode::KEY_VOLUMEDOWN]),
+    ("Mute", &[KeyCode::KEY_MUTE]),
+];
+
+/// Settings shared by all the sequence inputs, passed down from the app when they change
+#[derive(Debug, Clone, Default)]
+pub struct KeySeqOptions {
+    /// The user's presets, listed after [`BUILTIN_PRESETS`]
+    pub presets: Vec<KeyPreset>,
+    /// Add the numeric codes to the key names, see [`key_label`]
+    pub show_key_codes: bool,
+}
+
+/// Changes to the user's presets, to be persisted in the settings
+#[derive(Debug, Clone)]
+pub enum KeyPresetChange {
+    /// Add the keys as a preset, replacing one with the same name
+    Save(KeyPreset),
+    /// Remove the preset with this name
+    Remove(String),
+}
+
 #[derive(Debug)]
 struct KeyButton {
     key: KeyCode,
+    show_code: bool,
 }
 
 #[derive(Debug)]
 enum KeyButtonOutput {
     Remove(KeyCode),
+    /// A key was dragged onto this button
+    Move {
+        key: KeyCode,
+        to: DynamicIndex,
+    },
 }
 
 #[relm4::factory]
 impl FactoryComponent for KeyButton {
-    type Init = KeyCode;
+    type Init = (KeyCode, bool);
     type Input = ();
     type Output = KeyButtonOutput;
     type ParentWidget = gtk::Box;
@@ -28,16 +89,42 @@ impl FactoryComponent for KeyButton {
     view! {
         #[root]
         gtk::Button {
-            set_label: &format!("{}", EventCode::EV_KEY(self.key)),
-            set_tooltip_text: Some("Click to remove the key"),
+            set_label: &key_label(self.key, self.show_code),
+            set_tooltip_text: Some("Click to remove the key, drag to reorder"),
+            set_class_active: ("accent", is_modifier(&self.key)),
             connect_clicked[sender, keycode = self.key] => move |_| {
                 sender.output(KeyButtonOutput::Remove(keycode)).unwrap()
-            }
+            },
+
+            add_controller = gtk::DragSource {
+                set_actions: gdk::DragAction::MOVE,
+                connect_prepare[keycode = self.key] => move |_, _, _| {
+                    Some(gdk::ContentProvider::for_value(&(keycode as u32).to_value()))
+                }
+            },
+
+            // Keys are identified by their code, so keys dragged from another sequence are ignored
+            // by the receiving one unless it has them too
+            add_controller = gtk::DropTarget::new(u32::static_type(), gdk::DragAction::MOVE) {
+                connect_drop[sender, index] => move |_, value, _, _| {
+                    let Some(key) = value.get::<u32>().ok().and_then(int_to_ev_key) else {
+                        return false;
+                    };
+                    sender
+                        .output(KeyButtonOutput::Move { key, to: index.clone() })
+                        .unwrap();
+                    true
+                }
+            },
         }
     }
 
-    fn init_model(init: Self::Init, _index: &Self::Index, _sender: FactorySender<Self>) -> Self {
-        Self { key: init }
+    fn init_model(
+        (key, show_code): Self::Init,
+        _index: &Self::Index,
+        _sender: FactorySender<Self>,
+    ) -> Self {
+        Self { key, show_code }
     }
 }
 
@@ -45,38 +132,264 @@ impl FactoryComponent for KeyButton {
 pub struct KeySeqInput {
     pub sequence: KeyCombination,
     keys_factory: FactoryVecDeque<KeyButton>,
+    /// Keys the config's device can emit, if it's connected
+    supported_keys: Option<Vec<KeyCode>>,
+    /// List only [`Self::supported_keys`] in the dropdown
+    only_supported: bool,
+    /// Kind of keys listed in the dropdown
+    category: KeyCategory,
+    /// Items of the "Add key..." dropdown
+    key_names: gtk::StringList,
+    /// Read by the item factory of the "Add key..." dropdown when its items are shown
+    show_key_codes: Rc<Cell<bool>>,
+    options: KeySeqOptions,
+    presets_popover: gtk::Popover,
+    /// Show the problems of the sequence, set when saving and cleared when it's edited
+    show_problems: bool,
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum PasteKeyError {
+    #[error("The clipboard doesn't contain any text")]
+    NoText,
+    #[error("\"{0}\" is not a known key name")]
+    UnknownName(String),
+    #[error("Skipped unknown key names: {}", .0.join(", "))]
+    UnknownNames(Vec<String>),
+}
+
+#[derive(Debug)]
+pub enum KeySeqInputOutput {
+    /// The sequence was modified by the user
+    Changed,
+    PasteFailed(PasteKeyError),
+    Preset(KeyPresetChange),
 }
 
 #[derive(Debug)]
 pub enum KeySeqInputMsg {
     AddKey(KeyCode),
+    /// Replace the whole sequence, keeping the order of the keys
+    SetSequence(Vec<KeyCode>),
     ClearKeys,
     RemoveKey(KeyCode),
+    /// Move the key to the position
+    MoveKey(KeyCode, usize),
+    /// Move the modifiers in front of the other keys
+    SortModifiersFirst,
+    /// Add the key named by the text from the clipboard
+    PasteKeyName(Option<String>),
+    /// Replace the sequence with the list of keys from the clipboard
+    PasteSequence(Option<String>),
+    /// Keys the config's device can emit, [`None`] if it isn't connected
+    SetSupportedKeys(Option<Vec<KeyCode>>),
+    SetOnlySupported(bool),
+    SetCategory(KeyCategory),
+    /// Save the current sequence as a preset named after its keys
+    SaveAsPreset,
+    /// The presets or the key label format were changed in the settings
+    SetOptions(KeySeqOptions),
+    /// Show or hide the problems found with [`KeyCombination::validate`]
+    ShowProblems(bool),
 }
 
 impl KeySeqInput {
+    /// Fill the dropdown with the keys that can be added
+    fn key_names_update(&self) {
+        let listed: &[KeyCode] = match &self.supported_keys {
+            Some(keys) if self.only_supported => keys,
+            _ => list_keycodes(),
+        };
+        let mut listed: Vec<KeyCode> = listed
+            .iter()
+            .copied()
+            .filter(|k| self.category.contains(*k))
+            .collect();
+        // Same order as the sections of the dropdown, so that positions match
+        sort_by_group(&mut listed);
+        let names: Vec<String> = ["Add key...".to_owned()]
+            .into_iter()
+            .chain(listed.iter().map(|k| EventCode::EV_KEY(*k).to_string()))
+            .collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        self.key_names.splice(0, self.key_names.n_items(), &names);
+    }
+
     fn keys_factory_update(&mut self) {
         let mut kfac = self.keys_factory.guard();
         kfac.clear();
         for key in self.sequence.iter() {
-            kfac.push_back(key);
+            kfac.push_back((key, self.options.show_key_codes));
         }
     }
 }
 
-pub fn new_dropdown_property_expr() -> gtk::PropertyExpression {
-    gtk::PropertyExpression::new(
+/// Fill the presets popover with buttons for the built-in presets and the user's ones
+fn fill_presets_popover(
+    popover: &gtk::Popover,
+    user_presets: &[KeyPreset],
+    sender: &ComponentSender<KeySeqInput>,
+) {
+    let list = gtk::Box::new(gtk::Orientation::Vertical, 2);
+
+    let save_button = gtk::Button::with_label("Save current as preset");
+    save_button.set_has_frame(false);
+    save_button.connect_clicked({
+        let sender = sender.clone();
+        let popover = popover.clone();
+        move |_| {
+            sender.input(KeySeqInputMsg::SaveAsPreset);
+            popover.popdown();
+        }
+    });
+    list.append(&save_button);
+    list.append(&gtk::Separator::new(gtk::Orientation::Horizontal));
+
+    let presets = BUILTIN_PRESETS
+        .iter()
+        .map(|(name, keys)| (name.to_string(), keys.to_vec(), false))
+        .chain(
+            user_presets
+                .iter()
+                .map(|p| (p.name.clone(), p.keys.clone(), true)),
+        );
+    for (name, keys, removable) in presets {
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 2);
+        let insert_button = gtk::Button::with_label(&name);
+        insert_button.set_has_frame(false);
+        insert_button.set_hexpand(true);
+        insert_button.set_tooltip_text(Some("Replace the sequence with these keys"));
+        if let Some(label) = insert_button.child().and_downcast::<gtk::Label>() {
+            label.set_halign(gtk::Align::Start);
+        }
+        insert_button.connect_clicked({
+            let sender = sender.clone();
+            let popover = popover.clone();
+            move |_| {
+                sender.input(KeySeqInputMsg::SetSequence(keys.clone()));
+                popover.popdown();
+            }
+        });
+        row.append(&insert_button);
+        if removable {
+            let remove_button = gtk::Button::from_icon_name("user-trash-symbolic");
+            remove_button.set_has_frame(false);
+            remove_button.set_tooltip_text(Some("Remove preset"));
+            remove_button.connect_clicked({
+                let sender = sender.clone();
+                let popover = popover.clone();
+                move |_| {
+                    sender
+                        .output(KeySeqInputOutput::Preset(KeyPresetChange::Remove(
+                            name.clone(),
+                        )))
+                        .unwrap();
+                    popover.popdown();
+                }
+            });
+            row.append(&remove_button);
+        }
+        list.append(&row);
+    }
+    popover.set_child(Some(&list));
+}
+
+/// Text the key dropdowns are searched by: the key name together with its code, so that typing
+/// either `KEY_A` or `30` finds the key, whether the codes are shown or not
+pub fn new_key_dropdown_expr() -> gtk::ClosureExpression {
+    let name_expr = gtk::PropertyExpression::new(
         gtk::StringObject::static_type(),
         None::<gtk::Expression>,
         "string",
-    )
+    );
+    // The first value is the list item itself, the second one is its string
+    gtk::ClosureExpression::with_callback([name_expr], move |values| {
+        let name: String = values[1].get().unwrap_or_default();
+        match keycode_from_name(&name) {
+            Some(key) => key_label(key, true),
+            None => name,
+        }
+    })
+}
+
+/// Items of the key dropdowns, labeled with [`key_label`]. `show_codes` is read whenever an item
+/// is shown, the items have to be replaced for a change to take effect.
+pub fn new_key_item_factory(show_codes: Rc<Cell<bool>>) -> gtk::SignalListItemFactory {
+    let factory = gtk::SignalListItemFactory::new();
+    factory.connect_setup(|_, item| {
+        let Some(item) = item.downcast_ref::<gtk::ListItem>() else {
+            return;
+        };
+        item.set_child(Some(&gtk::Label::builder().xalign(0.0).build()));
+    });
+    factory.connect_bind(move |_, item| {
+        let Some(item) = item.downcast_ref::<gtk::ListItem>() else {
+            return;
+        };
+        let (Some(label), Some(name)) = (
+            item.child().and_downcast::<gtk::Label>(),
+            item.item().and_downcast::<gtk::StringObject>(),
+        ) else {
+            return;
+        };
+        let name = name.string();
+        match keycode_from_name(&name) {
+            Some(key) => label.set_label(&key_label(key, show_codes.get())),
+            None => label.set_label(&name),
+        }
+    });
+    factory
+}
+
+/// Group of a key dropdown item, [`None`] for placeholders like "Add key..."
+fn key_item_group(item: &glib::Object) -> Option<KeyGroup> {
+    item.downcast_ref::<gtk::StringObject>()
+        .and_then(|s| keycode_from_name(&s.string()))
+        .map(KeyGroup::of)
+}
+
+/// Split the key names into [`KeyGroup`] sections. The names have to be ordered by group
+/// already, otherwise the positions in the dropdown won't match the ones in the list.
+pub fn new_key_dropdown_model(names: &gtk::StringList) -> gtk::SortListModel {
+    let section_sorter =
+        gtk::CustomSorter::new(|a, b| key_item_group(a).cmp(&key_item_group(b)).into());
+    let model = gtk::SortListModel::new(Some(names.clone()), None::<gtk::Sorter>);
+    model.set_section_sorter(Some(&section_sorter));
+    model
+}
+
+/// Headers with the group name for the sections of [`new_key_dropdown_model`]
+pub fn new_key_group_header_factory() -> gtk::SignalListItemFactory {
+    let factory = gtk::SignalListItemFactory::new();
+    factory.connect_setup(|_, header| {
+        let Some(header) = header.downcast_ref::<gtk::ListHeader>() else {
+            return;
+        };
+        let label = gtk::Label::builder()
+            .xalign(0.0)
+            .css_classes(["heading"])
+            .build();
+        header.set_child(Some(&label));
+    });
+    factory.connect_bind(|_, header| {
+        let Some(header) = header.downcast_ref::<gtk::ListHeader>() else {
+            return;
+        };
+        let Some(label) = header.child().and_downcast::<gtk::Label>() else {
+            return;
+        };
+        let group = header.item().and_then(|item| key_item_group(&item));
+        label.set_label(group.map_or("", |g| g.label()));
+        label.set_visible(group.is_some());
+    });
+    factory
 }
 
 #[relm4::component(pub)]
 impl SimpleComponent for KeySeqInput {
-    type Init = Vec<KeyCode>;
+    type Init = (Vec<KeyCode>, KeySeqOptions);
     type Input = KeySeqInputMsg;
-    type Output = ();
+    type Output = KeySeqInputOutput;
 
     view! {
         gtk::Box {
@@ -92,29 +405,132 @@ impl SimpleComponent for KeySeqInput {
                 },
             },
 
+            gtk::DropDown::from_strings(&KeyCategory::ALL.map(|c| c.label())) {
+                set_tooltip_text: Some("Kind of keys listed"),
+                connect_selected_notify[sender] => move |dd| {
+                    if let Some(category) = KeyCategory::ALL.get(dd.selected() as usize) {
+                        sender.input(KeySeqInputMsg::SetCategory(*category));
+                    }
+                }
+            },
+
             gtk::DropDown::new(
-                Some(gtk::StringList::from_iter(["Add key...".to_owned()].into_iter().chain(list_keynames_iter()))),
-                Some(new_dropdown_property_expr())
+                Some(new_key_dropdown_model(&model.key_names)),
+                Some(new_key_dropdown_expr())
             ) {
+                set_factory: Some(&new_key_item_factory(model.show_key_codes.clone())),
                 set_enable_search: true,
                 set_search_match_mode: gtk::StringFilterMatchMode::Substring,
+                set_header_factory: Some(&new_key_group_header_factory()),
                 connect_selected_notify[sender] => move |dd| {
                     let idx = dd.selected();
                     if idx != gtk::INVALID_LIST_POSITION && idx != 0 {
-                        sender.input(KeySeqInputMsg::AddKey(list_keycodes()[(idx-1) as usize]));
+                        // The listed keys depend on the device, so look the key up by its name
+                        if let Some(key) = dd
+                            .selected_item()
+                            .and_downcast::<gtk::StringObject>()
+                            .and_then(|s| keycode_from_name(&s.string()))
+                        {
+                            sender.input(KeySeqInputMsg::AddKey(key));
+                        }
                         dd.set_selected(0);
                     }
                 }
             },
 
+            gtk::ToggleButton {
+                set_icon_name: "input-keyboard-symbolic",
+                set_tooltip_text: Some("Only list keys supported by the config's device"),
+                set_active: model.only_supported,
+                #[watch]
+                set_visible: model.supported_keys.is_some(),
+                connect_toggled[sender] => move |btn| {
+                    sender.input(KeySeqInputMsg::SetOnlySupported(btn.is_active()))
+                }
+            },
+
+            gtk::ToggleButton {
+                set_icon_name: "media-record-symbolic",
+                set_tooltip_text: Some("Record: press keys to add them to the sequence"),
+
+                // Capture phase, so that Space and Enter are recorded instead of toggling the button
+                add_controller = gtk::EventControllerKey {
+                    set_propagation_phase: gtk::PropagationPhase::Capture,
+                    connect_key_pressed[sender] => move |ctrl, _keyval, keycode, _state| {
+                        let recording = ctrl
+                            .widget()
+                            .and_downcast::<gtk::ToggleButton>()
+                            .is_some_and(|btn| btn.is_active());
+                        if !recording {
+                            return glib::Propagation::Proceed;
+                        }
+                        // Keys without an evdev counterpart are ignored
+                        if let Some(key) = key_from_hardware_keycode(keycode) {
+                            sender.input(KeySeqInputMsg::AddKey(key));
+                        }
+                        glib::Propagation::Stop
+                    }
+                },
+
+                add_controller = gtk::EventControllerFocus {
+                    connect_leave => |ctrl| {
+                        if let Some(btn) = ctrl.widget().and_downcast::<gtk::ToggleButton>() {
+                            btn.set_active(false);
+                        }
+                    }
+                },
+            },
+
+            gtk::Button::from_icon_name("view-sort-ascending-symbolic") {
+                set_tooltip_text: Some("Move modifiers in front of the other keys"),
+                #[watch]
+                set_sensitive: model.sequence.has_key_before_modifier(),
+                connect_clicked => KeySeqInputMsg::SortModifiersFirst,
+            },
+
+            gtk::Button::from_icon_name("edit-paste-symbolic") {
+                set_tooltip_text: Some("Paste key name"),
+                connect_clicked[sender] => move |btn| {
+                    let sender = sender.clone();
+                    btn.clipboard().read_text_async(None::<&gtk::gio::Cancellable>, move |text| {
+                        let text = text.ok().flatten().map(|t| t.to_string());
+                        sender.input(KeySeqInputMsg::PasteKeyName(text));
+                    });
+                }
+            },
+
+            gtk::Button::from_icon_name("insert-text-symbolic") {
+                set_tooltip_text: Some("Paste sequence, like [\"KEY_LEFTCTRL\", \"KEY_C\"]"),
+                connect_clicked[sender] => move |btn| {
+                    let sender = sender.clone();
+                    btn.clipboard().read_text_async(None::<&gtk::gio::Cancellable>, move |text| {
+                        let text = text.ok().flatten().map(|t| t.to_string());
+                        sender.input(KeySeqInputMsg::PasteSequence(text));
+                    });
+                }
+            },
+
+            gtk::MenuButton {
+                set_icon_name: "view-list-bullet-symbolic",
+                set_tooltip_text: Some("Key sequence presets"),
+                set_popover: Some(presets_popover),
+            },
+
             gtk::Button::from_icon_name("edit-clear-symbolic") {
                 connect_clicked => KeySeqInputMsg::ClearKeys,
             },
+
+            gtk::Image::from_icon_name("dialog-warning-symbolic") {
+                #[watch]
+                set_visible: model.show_problems && model.sequence.validate().is_err(),
+                #[watch]
+                set_tooltip_text: model.sequence.validate().err().map(|e| e.to_string()).as_deref(),
+            },
         }
     }
 
     fn init(
-        init: Self::Init,
+        (initial_keys, options): Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
@@ -123,31 +539,144 @@ impl SimpleComponent for KeySeqInput {
                 .launch_default()
                 .forward(sender.input_sender(), |msg| match msg {
                     KeyButtonOutput::Remove(key) => KeySeqInputMsg::RemoveKey(key),
+                    KeyButtonOutput::Move { key, to } => {
+                        KeySeqInputMsg::MoveKey(key, to.current_index())
+                    }
                 });
 
-        let model = Self {
-            sequence: init.into(),
+        let sequence = KeyCombination::from(initial_keys);
+
+        let mut model = Self {
+            sequence,
             keys_factory: keys,
+            supported_keys: None,
+            only_supported: true,
+            category: KeyCategory::default(),
+            key_names: gtk::StringList::default(),
+            show_key_codes: Rc::new(Cell::new(options.show_key_codes)),
+            options,
+            presets_popover: gtk::Popover::default(),
+            show_problems: false,
         };
+        model.key_names_update();
+        model.keys_factory_update();
+        fill_presets_popover(&model.presets_popover, &model.options.presets, &sender);
 
         let keys_factory_box = model.keys_factory.widget();
+        let presets_popover = &model.presets_popover;
         let widgets = view_output!();
 
         ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>) {
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
         match message {
             KeySeqInputMsg::AddKey(k) => {
                 self.sequence.push(k);
             }
+            KeySeqInputMsg::SetSequence(keys) => {
+                self.sequence = KeyCombination::from(keys);
+            }
             KeySeqInputMsg::ClearKeys => {
                 self.sequence.clear();
             }
             KeySeqInputMsg::RemoveKey(key) => {
                 self.sequence.remove(key);
             }
+            KeySeqInputMsg::MoveKey(key, to) => {
+                // Dropped from another sequence
+                if !self.sequence.contains(key) {
+                    return;
+                }
+                self.sequence.move_key(key, to);
+            }
+            KeySeqInputMsg::SortModifiersFirst => {
+                self.sequence.sort_modifiers_first();
+            }
+            KeySeqInputMsg::SetSupportedKeys(keys) => {
+                if self.supported_keys != keys {
+                    self.supported_keys = keys;
+                    self.key_names_update();
+                }
+                // The sequence itself isn't changed
+                return;
+            }
+            KeySeqInputMsg::SetOnlySupported(only) => {
+                self.only_supported = only;
+                self.key_names_update();
+                return;
+            }
+            KeySeqInputMsg::SetCategory(category) => {
+                self.category = category;
+                self.key_names_update();
+                return;
+            }
+            KeySeqInputMsg::ShowProblems(show) => {
+                self.show_problems = show;
+                return;
+            }
+            KeySeqInputMsg::SetOptions(options) => {
+                let codes_changed = options.show_key_codes != self.options.show_key_codes;
+                self.options = options;
+                fill_presets_popover(&self.presets_popover, &self.options.presets, &sender);
+                if codes_changed {
+                    self.show_key_codes.set(self.options.show_key_codes);
+                    self.key_names_update();
+                    self.keys_factory_update();
+                }
+                return;
+            }
+            KeySeqInputMsg::SaveAsPreset => {
+                let keys = self.sequence.to_keys();
+                if !keys.is_empty() {
+                    let name = summarize_keys(keys.iter().copied());
+                    sender
+                        .output(KeySeqInputOutput::Preset(KeyPresetChange::Save(
+                            KeyPreset { name, keys },
+                        )))
+                        .unwrap();
+                }
+                return;
+            }
+            KeySeqInputMsg::PasteKeyName(text) => {
+                let text = text.filter(|t| !t.trim().is_empty());
+                let result = match &text {
+                    None => Err(PasteKeyError::NoText),
+                    Some(text) => keycode_from_name(text)
+                        .ok_or_else(|| PasteKeyError::UnknownName(text.trim().to_owned())),
+                };
+                match result {
+                    Ok(key) => self.sequence.push(key),
+                    Err(e) => {
+                        sender.output(KeySeqInputOutput::PasteFailed(e)).unwrap();
+                        return;
+                    }
+                }
+            }
+            KeySeqInputMsg::PasteSequence(text) => {
+                let Some(text) = text.filter(|t| !t.trim().is_empty()) else {
+                    sender
+                        .output(KeySeqInputOutput::PasteFailed(PasteKeyError::NoText))
+                        .unwrap();
+                    return;
+                };
+                let (keys, unknown) = parse_key_list(&text);
+                if !unknown.is_empty() {
+                    sender
+                        .output(KeySeqInputOutput::PasteFailed(PasteKeyError::UnknownNames(
+                            unknown,
+                        )))
+                        .unwrap();
+                }
+                // Nothing usable was pasted, keep the sequence
+                if keys.is_empty() {
+                    return;
+                }
+                self.sequence = KeyCombination::from(keys);
+            }
         }
-        self.keys_factory_update()
+        self.show_problems = false;
+        self.keys_factory_update();
+        sender.output(KeySeqInputOutput::Changed).unwrap();
     }
 }
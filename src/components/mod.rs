@@ -1,5 +1,9 @@
+pub mod changes_dialog;
+pub mod config_preview;
 pub mod device_browser;
+pub mod device_picker;
 pub mod dual_role;
 pub mod event_logger;
 pub mod key_seq;
+pub mod preferences;
 pub mod remap;
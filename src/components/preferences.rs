@@ -0,0 +1,229 @@
+use relm4::{adw::prelude::*, prelude::*};
+
+use crate::{
+    deviceinfo::{DeviceKindFilter, DeviceSort},
+    settings::Settings,
+};
+
+#[derive(Debug)]
+pub struct PreferencesDialog {
+    settings: Settings,
+}
+
+#[derive(Debug)]
+pub enum PreferencesMsg {
+    DeviceSort(DeviceSort),
+    DeviceKind(DeviceKindFilter),
+    AutoRefresh(bool),
+    ConfirmDeleteEntry(bool),
+    ConfirmClearAll(bool),
+    ConfirmRevert(bool),
+    ConfirmDiscardOnClose(bool),
+    BackupOnSave(bool),
+    MaxLogLines(u32),
+    EditorOnly(bool),
+    ShowKeyCodes(bool),
+    /// Settings were changed outside the dialog, replace the local copy without emitting output
+    Sync(Settings),
+}
+
+#[derive(Debug)]
+pub enum PreferencesOutput {
+    /// Settings were changed by the user and should be persisted
+    Changed(Settings),
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for PreferencesDialog {
+    type Init = Settings;
+    type Input = PreferencesMsg;
+    type Output = PreferencesOutput;
+
+    view! {
+        adw::PreferencesDialog {
+            set_title: "Preferences",
+
+            add = &adw::PreferencesPage {
+                set_title: "General",
+                set_icon_name: Some("preferences-system-symbolic"),
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Interface",
+
+                    add = &adw::SwitchRow {
+                        set_title: "Editor only",
+                        set_subtitle: "Hide the device browser and the event logger, takes effect after a restart",
+                        set_active: model.settings.editor_only,
+                        connect_active_notify[sender] => move |row| {
+                            sender.input(PreferencesMsg::EditorOnly(row.is_active()))
+                        }
+                    },
+
+                    add = &adw::SwitchRow {
+                        set_title: "Show key codes",
+                        set_subtitle: "Add the numeric evdev code to key names, like KEY_A (30)",
+                        set_active: !model.settings.hide_key_codes,
+                        connect_active_notify[sender] => move |row| {
+                            sender.input(PreferencesMsg::ShowKeyCodes(row.is_active()))
+                        }
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Device browser",
+                    set_description: Some("Applied to the device list when the app starts"),
+
+                    add = &adw::ComboRow {
+                        set_title: "Sort devices by",
+                        set_model: Some(&gtk::StringList::from_iter(DeviceSort::ALL.map(|s| s.label()))),
+                        set_selected: DeviceSort::ALL
+                            .iter()
+                            .position(|s| *s == model.settings.device_browser.sort)
+                            .unwrap_or(0) as u32,
+                        connect_selected_notify[sender] => move |row| {
+                            if let Some(sort) = DeviceSort::ALL.get(row.selected() as usize) {
+                                sender.input(PreferencesMsg::DeviceSort(*sort));
+                            }
+                        }
+                    },
+
+                    add = &adw::ComboRow {
+                        set_title: "Show",
+                        set_model: Some(&gtk::StringList::from_iter(DeviceKindFilter::ALL.map(|k| k.label()))),
+                        set_selected: DeviceKindFilter::ALL
+                            .iter()
+                            .position(|k| *k == model.settings.device_browser.kind)
+                            .unwrap_or(0) as u32,
+                        connect_selected_notify[sender] => move |row| {
+                            if let Some(kind) = DeviceKindFilter::ALL.get(row.selected() as usize) {
+                                sender.input(PreferencesMsg::DeviceKind(*kind));
+                            }
+                        }
+                    },
+
+                    add = &adw::SwitchRow {
+                        set_title: "Refresh on hotplug",
+                        set_subtitle: "Rescan the devices when one is plugged in or removed",
+                        set_active: model.settings.device_browser.auto_refresh,
+                        connect_active_notify[sender] => move |row| {
+                            sender.input(PreferencesMsg::AutoRefresh(row.is_active()))
+                        }
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Confirmations",
+                    set_description: Some("Ask before performing destructive actions"),
+
+                    add = &adw::SwitchRow {
+                        set_title: "Deleting an entry",
+                        set_subtitle: "Remap and dual-role entries",
+                        set_active: model.settings.confirmations.delete_entry,
+                        connect_active_notify[sender] => move |row| {
+                            sender.input(PreferencesMsg::ConfirmDeleteEntry(row.is_active()))
+                        }
+                    },
+
+                    add = &adw::SwitchRow {
+                        set_title: "Deleting all entries",
+                        set_subtitle: "All remaps or all dual-role entries at once",
+                        set_active: model.settings.confirmations.clear_all,
+                        connect_active_notify[sender] => move |row| {
+                            sender.input(PreferencesMsg::ConfirmClearAll(row.is_active()))
+                        }
+                    },
+
+                    add = &adw::SwitchRow {
+                        set_title: "Reverting to the saved file",
+                        set_subtitle: "Unsaved changes and the undo history are lost",
+                        set_active: model.settings.confirmations.revert,
+                        connect_active_notify[sender] => move |row| {
+                            sender.input(PreferencesMsg::ConfirmRevert(row.is_active()))
+                        }
+                    },
+
+                    add = &adw::SwitchRow {
+                        set_title: "Closing with unsaved changes",
+                        set_subtitle: "Otherwise the changes are discarded without asking",
+                        set_active: model.settings.confirmations.discard_on_close,
+                        connect_active_notify[sender] => move |row| {
+                            sender.input(PreferencesMsg::ConfirmDiscardOnClose(row.is_active()))
+                        }
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Saving",
+
+                    add = &adw::SwitchRow {
+                        set_title: "Back up the previous version",
+                        set_subtitle: "Keep a copy with the .bak extension next to the file before overwriting it",
+                        set_active: model.settings.saving.backup,
+                        connect_active_notify[sender] => move |row| {
+                            sender.input(PreferencesMsg::BackupOnSave(row.is_active()))
+                        }
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Event logger",
+
+                    add = &adw::SpinRow::with_range(100.0, 100_000.0, 100.0) {
+                        set_title: "Max log lines",
+                        set_subtitle: "The oldest lines are removed from the log past this count",
+                        // Can also be changed on the event logger page
+                        #[watch]
+                        set_value: model.settings.event_logger.max_lines as f64,
+                        connect_value_notify[sender] => move |row| {
+                            sender.input(PreferencesMsg::MaxLogLines(row.value() as u32))
+                        }
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = Self { settings: init };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        let browser = &mut self.settings.device_browser;
+        let confirmations = &mut self.settings.confirmations;
+        match message {
+            PreferencesMsg::DeviceSort(sort) => browser.sort = sort,
+            PreferencesMsg::DeviceKind(kind) => browser.kind = kind,
+            PreferencesMsg::AutoRefresh(auto_refresh) => browser.auto_refresh = auto_refresh,
+            PreferencesMsg::ConfirmDeleteEntry(confirm) => confirmations.delete_entry = confirm,
+            PreferencesMsg::ConfirmClearAll(confirm) => confirmations.clear_all = confirm,
+            PreferencesMsg::ConfirmRevert(confirm) => confirmations.revert = confirm,
+            PreferencesMsg::ConfirmDiscardOnClose(confirm) => {
+                confirmations.discard_on_close = confirm
+            }
+            PreferencesMsg::BackupOnSave(backup) => self.settings.saving.backup = backup,
+            PreferencesMsg::MaxLogLines(max_lines) => {
+                if self.settings.event_logger.max_lines == max_lines {
+                    return;
+                }
+                self.settings.event_logger.max_lines = max_lines
+            }
+            PreferencesMsg::EditorOnly(editor_only) => self.settings.editor_only = editor_only,
+            PreferencesMsg::ShowKeyCodes(show) => self.settings.hide_key_codes = !show,
+            PreferencesMsg::Sync(settings) => {
+                self.settings = settings;
+                return;
+            }
+        }
+        sender
+            .output(PreferencesOutput::Changed(self.settings.clone()))
+            .unwrap();
+    }
+}
@@ -1,24 +1,109 @@
+use std::collections::HashSet;
+
 use gtk::prelude::*;
 use relm4::{gtk, prelude::*};
 
-use super::key_seq::KeySeqInput;
-use crate::config_file::RemapConfig;
+use super::key_seq::{
+    KeyPresetChange, KeySeqInput, KeySeqInputMsg, KeySeqInputOutput, KeySeqOptions, PasteKeyError,
+};
+use crate::{
+    config_file::{RemapConfig, RemapIssue},
+    evdev_utils::{KeyCode, unseen_keys_hint},
+};
 
 #[derive(Debug)]
 pub struct RemapItem {
     pub input_seq: Controller<KeySeqInput>,
     pub output_seq: Controller<KeySeqInput>,
+    issue: Option<RemapIssue>,
+    /// Another remap has the same input keys
+    conflicting: bool,
+    /// Problems were found in the remap when saving
+    save_problem: bool,
+    /// Keys the config's device supports but that weren't observed from it in the event logger
+    /// this session
+    unseen_keys: Option<HashSet<KeyCode>>,
+    /// The sequences are shown, otherwise only a one-line summary
+    expanded: bool,
+    comment: gtk::EntryBuffer,
+}
+
+#[derive(Debug, Clone)]
+pub enum RemapItemMsg {
+    SequenceChanged,
+    SetUnseenKeys(Option<HashSet<KeyCode>>),
+    /// Keys the config's device can emit, only they can be used as the input
+    SetSupportedKeys(Option<Vec<KeyCode>>),
+    PasteFailed(PasteKeyError),
+    Preset(KeyPresetChange),
+    SetConflict(bool),
+    SetSaveProblem(bool),
+    /// The presets or the key label format were changed in the settings
+    SetKeySeqOptions(KeySeqOptions),
+    /// Exchange the input and output keys
+    SwapSequences,
+    SetExpanded(bool),
+    ToggleExpanded,
+    CommentChanged,
 }
 
 #[derive(Debug)]
 pub enum RemapItemOutput {
     Delete(DynamicIndex),
+    MoveUp(DynamicIndex),
+    MoveDown(DynamicIndex),
+    /// Insert a copy of the entry right after it
+    Duplicate(DynamicIndex),
+    /// One of the sequences was edited
+    Changed,
+    /// The comment was edited
+    CommentChanged,
+    PasteFailed(PasteKeyError),
+    Preset(KeyPresetChange),
+}
+
+impl RemapItem {
+    pub fn to_config(&self) -> RemapConfig {
+        // Saved as a single comment line
+        let comment = self
+            .comment
+            .text()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        RemapConfig {
+            input: self.input_seq.model().sequence.to_keys(),
+            output: self.output_seq.model().sequence.to_keys(),
+            comment: (!comment.is_empty()).then_some(comment),
+        }
+    }
+
+    /// One-line summary shown while the remap is collapsed
+    fn collapsed_summary(&self) -> String {
+        let config = self.to_config();
+        match &config.comment {
+            Some(comment) => format!("{} — {comment}", config.summary()),
+            None => config.summary(),
+        }
+    }
+
+    /// Informational messages about the remap that don't prevent saving it
+    fn hints(&self) -> Vec<String> {
+        self.issue
+            .map(|i| i.to_string())
+            .into_iter()
+            .chain(unseen_keys_hint(
+                self.input_seq.model().sequence.iter(),
+                self.unseen_keys.as_ref(),
+            ))
+            .collect()
+    }
 }
 
 #[relm4::factory(pub)]
 impl FactoryComponent for RemapItem {
-    type Init = RemapConfig;
-    type Input = ();
+    type Init = (RemapConfig, KeySeqOptions);
+    type Input = RemapItemMsg;
     type Output = RemapItemOutput;
     type CommandOutput = ();
     type ParentWidget = gtk::Box;
@@ -26,14 +111,45 @@ impl FactoryComponent for RemapItem {
     view! {
         #[root]
         gtk::Frame {
+            #[watch]
+            set_class_active: ("error", self.conflicting),
+            #[watch]
+            set_class_active: ("warning", self.save_problem),
+            #[watch]
+            set_tooltip_text: self
+                .conflicting
+                .then_some("Another remap has the same input keys, evremap's behavior is undefined"),
+
             gtk::Box {
                 set_orientation: gtk::Orientation::Horizontal,
                 set_spacing: 12,
                 set_margin_all: 12,
 
+                gtk::Button {
+                    set_has_frame: false,
+                    set_valign: gtk::Align::Center,
+                    #[watch]
+                    set_icon_name: if self.expanded { "pan-down-symbolic" } else { "pan-end-symbolic" },
+                    #[watch]
+                    set_tooltip_text: Some(if self.expanded { "Collapse" } else { "Expand" }),
+                    connect_clicked => RemapItemMsg::ToggleExpanded,
+                },
+
+                gtk::Label {
+                    set_hexpand: true,
+                    set_halign: gtk::Align::Start,
+                    set_ellipsize: gtk::pango::EllipsizeMode::End,
+                    #[watch]
+                    set_visible: !self.expanded,
+                    #[watch]
+                    set_label: &self.collapsed_summary(),
+                },
+
                 gtk::Grid {
                     set_row_spacing: 6,
                     set_column_spacing: 6,
+                    #[watch]
+                    set_visible: self.expanded,
 
                     attach[0,0,1,1] = &gtk::Label {
                         set_label: "Input:",
@@ -48,6 +164,60 @@ impl FactoryComponent for RemapItem {
                     },
 
                     attach[1,1,1,1] = self.output_seq.widget(),
+
+                    attach[0,2,1,1] = &gtk::Label {
+                        set_label: "Comment:",
+                        set_halign: gtk::Align::Start,
+                    },
+
+                    attach[1,2,1,1] = &gtk::Entry {
+                        set_placeholder_text: Some("What the remap is for (optional)"),
+                        set_buffer: &self.comment,
+                        connect_changed => RemapItemMsg::CommentChanged,
+                    },
+                },
+
+                gtk::Button::from_icon_name("object-flip-vertical-symbolic") {
+                    #[watch]
+                    set_visible: self.expanded,
+                    set_has_frame: false,
+                    set_tooltip_text: Some("Swap input and output"),
+                    connect_clicked => RemapItemMsg::SwapSequences,
+                },
+
+                gtk::Image::from_icon_name("dialog-information-symbolic") {
+                    #[watch]
+                    set_visible: !self.hints().is_empty(),
+                    #[watch]
+                    set_tooltip_text: Some(&self.hints().join("\n")),
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_valign: gtk::Align::Center,
+
+                    gtk::Button::from_icon_name("go-up-symbolic") {
+                        set_has_frame: false,
+                        set_tooltip_text: Some("Move up"),
+                        connect_clicked[sender, index] => move |_| {
+                            sender.output(RemapItemOutput::MoveUp(index.clone())).unwrap();
+                        }
+                    },
+
+                    gtk::Button::from_icon_name("go-down-symbolic") {
+                        set_has_frame: false,
+                        set_tooltip_text: Some("Move down"),
+                        connect_clicked[sender, index] => move |_| {
+                            sender.output(RemapItemOutput::MoveDown(index.clone())).unwrap();
+                        }
+                    },
+                },
+
+                gtk::Button::from_icon_name("edit-copy-symbolic") {
+                    set_tooltip_text: Some("Duplicate"),
+                    connect_clicked[sender, index] => move |_| {
+                        sender.output(RemapItemOutput::Duplicate(index.clone())).unwrap();
+                    }
                 },
 
                 gtk::Button::from_icon_name("edit-delete-symbolic") {
@@ -59,12 +229,70 @@ impl FactoryComponent for RemapItem {
         }
     }
 
-    fn init_model(init: Self::Init, _index: &Self::Index, _sender: FactorySender<Self>) -> Self {
-        let input_seq = KeySeqInput::builder().launch(init.input).detach();
-        let output_seq = KeySeqInput::builder().launch(init.output).detach();
+    fn init_model(
+        (init, options): Self::Init,
+        _index: &Self::Index,
+        sender: FactorySender<Self>,
+    ) -> Self {
+        let issue = init.check();
+        let input_seq = KeySeqInput::builder()
+            .launch((init.input, options.clone()))
+            .forward(sender.input_sender(), |out| match out {
+                KeySeqInputOutput::Changed => RemapItemMsg::SequenceChanged,
+                KeySeqInputOutput::PasteFailed(e) => RemapItemMsg::PasteFailed(e),
+                KeySeqInputOutput::Preset(change) => RemapItemMsg::Preset(change),
+            });
+        let output_seq = KeySeqInput::builder()
+            .launch((init.output, options))
+            .forward(sender.input_sender(), |out| match out {
+                KeySeqInputOutput::Changed => RemapItemMsg::SequenceChanged,
+                KeySeqInputOutput::PasteFailed(e) => RemapItemMsg::PasteFailed(e),
+                KeySeqInputOutput::Preset(change) => RemapItemMsg::Preset(change),
+            });
         Self {
             input_seq,
             output_seq,
+            issue,
+            conflicting: false,
+            save_problem: false,
+            unseen_keys: None,
+            expanded: true,
+            comment: gtk::EntryBuffer::new(init.comment),
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: FactorySender<Self>) {
+        match message {
+            RemapItemMsg::SequenceChanged => {
+                self.issue = self.to_config().check();
+                self.save_problem = false;
+                sender.output(RemapItemOutput::Changed).unwrap();
+            }
+            RemapItemMsg::SetUnseenKeys(keys) => self.unseen_keys = keys,
+            RemapItemMsg::SetExpanded(expanded) => self.expanded = expanded,
+            RemapItemMsg::ToggleExpanded => self.expanded = !self.expanded,
+            RemapItemMsg::CommentChanged => sender.output(RemapItemOutput::CommentChanged).unwrap(),
+            RemapItemMsg::SetSupportedKeys(keys) => {
+                self.input_seq.emit(KeySeqInputMsg::SetSupportedKeys(keys))
+            }
+            RemapItemMsg::SetKeySeqOptions(options) => {
+                self.input_seq
+                    .emit(KeySeqInputMsg::SetOptions(options.clone()));
+                self.output_seq.emit(KeySeqInputMsg::SetOptions(options));
+            }
+            RemapItemMsg::SetConflict(conflicting) => self.conflicting = conflicting,
+            RemapItemMsg::SetSaveProblem(problem) => {
+                self.save_problem = problem;
+                self.input_seq.emit(KeySeqInputMsg::ShowProblems(problem));
+                self.output_seq.emit(KeySeqInputMsg::ShowProblems(problem));
+            }
+            RemapItemMsg::SwapSequences => {
+                let RemapConfig { input, output, .. } = self.to_config();
+                self.input_seq.emit(KeySeqInputMsg::SetSequence(output));
+                self.output_seq.emit(KeySeqInputMsg::SetSequence(input));
+            }
+            RemapItemMsg::PasteFailed(e) => sender.output(RemapItemOutput::PasteFailed(e)).unwrap(),
+            RemapItemMsg::Preset(change) => sender.output(RemapItemOutput::Preset(change)).unwrap(),
         }
     }
 }
@@ -0,0 +1,262 @@
+//! Structural comparison of two config files
+
+use std::fmt::Display;
+
+use crate::{
+    config_file::{ConfigFile, DualRoleConfig, RemapConfig},
+    evdev_utils::summarize_keys,
+};
+
+#[derive(Debug, Clone)]
+pub enum EntryChange<T> {
+    Added(T),
+    Removed(T),
+    Changed { old: T, new: T },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConfigDiff {
+    /// Old and new values, if different
+    pub device_name: Option<(Option<String>, Option<String>)>,
+    /// Old and new values, if different
+    pub phys: Option<(Option<String>, Option<String>)>,
+    pub remap: Vec<EntryChange<RemapConfig>>,
+    pub dual_role: Vec<EntryChange<DualRoleConfig>>,
+}
+
+impl ConfigDiff {
+    /// Compare two configs. Remaps are matched up by their input keys and dual-role entries by
+    /// their trigger key, so that editing the output shows up as a change rather than a removal
+    /// and an addition.
+    pub fn new(old: &ConfigFile, new: &ConfigFile) -> Self {
+        Self {
+            device_name: (old.device_name != new.device_name)
+                .then(|| (old.device_name.clone(), new.device_name.clone())),
+            phys: (old.phys != new.phys).then(|| (old.phys.clone(), new.phys.clone())),
+            remap: diff_entries(
+                &old.remap,
+                &new.remap,
+                |a, b| a.same_input(b),
+                RemapConfig::same_as,
+            ),
+            dual_role: diff_entries(
+                &old.dual_role,
+                &new.dual_role,
+                |a, b| a.input == b.input,
+                DualRoleConfig::same_as,
+            ),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.device_name.is_none()
+            && self.phys.is_none()
+            && self.remap.is_empty()
+            && self.dual_role.is_empty()
+    }
+}
+
+fn diff_entries<T: Clone>(
+    old: &[T],
+    new: &[T],
+    same_key: impl Fn(&T, &T) -> bool,
+    same_entry: impl Fn(&T, &T) -> bool,
+) -> Vec<EntryChange<T>> {
+    let mut changes = vec![];
+    for old_entry in old {
+        match new.iter().find(|n| same_key(old_entry, n)) {
+            None => changes.push(EntryChange::Removed(old_entry.clone())),
+            Some(new_entry) if !same_entry(old_entry, new_entry) => {
+                changes.push(EntryChange::Changed {
+                    old: old_entry.clone(),
+                    new: new_entry.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for new_entry in new {
+        if !old.iter().any(|o| same_key(o, new_entry)) {
+            changes.push(EntryChange::Added(new_entry.clone()));
+        }
+    }
+    changes
+}
+
+/// Line of a [`diff_lines`] result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Line-based diff of two texts, like the serialized configs before and after editing. Keeps
+/// the longest common subsequence of lines, which is fine for files the size of a config.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // Length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|line| DiffLine::Removed(line)));
+    lines.extend(new[j..].iter().map(|line| DiffLine::Added(line)));
+    lines
+}
+
+/// Prefixed with `-` for removed and `+` for added lines, like in a unified diff
+impl Display for DiffLine<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffLine::Same(line) => write!(f, "  {line}"),
+            DiffLine::Removed(line) => write!(f, "- {line}"),
+            DiffLine::Added(line) => write!(f, "+ {line}"),
+        }
+    }
+}
+
+fn fmt_value(value: &Option<String>) -> String {
+    value
+        .as_ref()
+        .map_or("(unset)".to_owned(), |v| format!("\"{v}\""))
+}
+
+impl Display for ConfigDiff {
+    /// One change per line: `+` for added, `-` for removed and `~` for changed entries
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some((old, new)) = &self.device_name {
+            writeln!(f, "~ device_name: {} -> {}", fmt_value(old), fmt_value(new))?;
+        }
+        if let Some((old, new)) = &self.phys {
+            writeln!(f, "~ phys: {} -> {}", fmt_value(old), fmt_value(new))?;
+        }
+        for change in &self.remap {
+            match change {
+                EntryChange::Added(r) => writeln!(f, "+ remap {}", r.summary())?,
+                EntryChange::Removed(r) => writeln!(f, "- remap {}", r.summary())?,
+                EntryChange::Changed { old, new } => writeln!(
+                    f,
+                    "~ remap {}: output {} -> {}",
+                    summarize_keys(old.input.iter().copied()),
+                    summarize_keys(old.output.iter().copied()),
+                    summarize_keys(new.output.iter().copied())
+                )?,
+            }
+        }
+        for change in &self.dual_role {
+            match change {
+                EntryChange::Added(d) => writeln!(f, "+ dual_role {}", d.summary())?,
+                EntryChange::Removed(d) => writeln!(f, "- dual_role {}", d.summary())?,
+                EntryChange::Changed { old, new } => {
+                    writeln!(f, "~ dual_role {} -> {}", old.summary(), new.summary())?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evdev_utils::KeyCode;
+
+    fn remap(input: &[KeyCode], output: &[KeyCode]) -> RemapConfig {
+        RemapConfig {
+            input: input.to_vec(),
+            output: output.to_vec(),
+            comment: None,
+        }
+    }
+
+    fn diff_remaps(old: &[RemapConfig], new: &[RemapConfig]) -> Vec<EntryChange<RemapConfig>> {
+        diff_entries(old, new, |a, b| a.same_input(b), RemapConfig::same_as)
+    }
+
+    #[test]
+    fn entries_added_removed_and_changed() {
+        let old = [
+            remap(&[KeyCode::KEY_A], &[KeyCode::KEY_B]),
+            remap(&[KeyCode::KEY_C], &[KeyCode::KEY_D]),
+        ];
+        let new = [
+            remap(&[KeyCode::KEY_A], &[KeyCode::KEY_X]),
+            remap(&[KeyCode::KEY_E], &[KeyCode::KEY_F]),
+        ];
+        let changes = diff_remaps(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert!(matches!(
+            &changes[0],
+            EntryChange::Changed { old, new }
+                if old.output == [KeyCode::KEY_B] && new.output == [KeyCode::KEY_X]
+        ));
+        assert!(matches!(&changes[1], EntryChange::Removed(r) if r.input == [KeyCode::KEY_C]));
+        assert!(matches!(&changes[2], EntryChange::Added(r) if r.input == [KeyCode::KEY_E]));
+    }
+
+    #[test]
+    fn reordered_entries_are_not_changes() {
+        let old = [
+            remap(&[KeyCode::KEY_A], &[KeyCode::KEY_B]),
+            remap(&[KeyCode::KEY_C], &[KeyCode::KEY_D]),
+        ];
+        let new = [old[1].clone(), old[0].clone()];
+        assert!(diff_remaps(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn empty_configs_have_no_changes() {
+        assert!(ConfigDiff::new(&ConfigFile::default(), &ConfigFile::default()).is_empty());
+        assert!(diff_lines("", "").is_empty());
+    }
+
+    #[test]
+    fn everything_is_added_to_an_empty_config() {
+        let new = [remap(&[KeyCode::KEY_A], &[KeyCode::KEY_B])];
+        let changes = diff_remaps(&[], &new);
+        assert!(matches!(changes.as_slice(), [EntryChange::Added(_)]));
+        assert_eq!(
+            diff_lines("", "a\nb"),
+            [DiffLine::Added("a"), DiffLine::Added("b")]
+        );
+    }
+
+    #[test]
+    fn lines_inserted_removed_and_replaced() {
+        assert_eq!(
+            diff_lines("a\nb\nc\nd", "a\nx\nc\nd\ne"),
+            [
+                DiffLine::Same("a"),
+                DiffLine::Removed("b"),
+                DiffLine::Added("x"),
+                DiffLine::Same("c"),
+                DiffLine::Same("d"),
+                DiffLine::Added("e"),
+            ]
+        );
+    }
+}
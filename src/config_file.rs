@@ -1,10 +1,18 @@
-use std::path::Path;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+};
 
 // The contents of this file are loosely based on [`evremap`](https://github.com/wez/evremap/blob/master/src/mapping.rs#L116)
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, Value};
 
-use crate::evdev_utils::{KeyCode, list_keycodes};
+use crate::{
+    evdev_utils::{KeyCode, list_keycodes, summarize_keys},
+    key_combo::validate_keys,
+};
 
 #[derive(Debug, Error)]
 pub enum ConfigFileError {
@@ -14,9 +22,48 @@ pub enum ConfigFileError {
     TomlDeserialize(#[from] toml::de::Error),
     #[error("Serialization error: {0}")]
     TomlSerialize(#[from] toml::ser::Error),
+    #[error("Parsing error: {0}")]
+    TomlEdit(#[from] toml_edit::TomlError),
+    #[error("The written config would not be read back the same, this is a bug")]
+    RoundTrip,
+    #[error("Failed to run pkexec: {0}")]
+    PkexecSpawn(std::io::Error),
+    #[error("Saving as administrator needs the helper at {}, see the README", .0.display())]
+    SaveHelperMissing(PathBuf),
+    #[error("Authentication was cancelled or failed")]
+    PkexecNotAuthorized,
+    #[error("Copying the config into place failed: {0}")]
+    PrivilegedCopy(ExitStatus),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Keys unknown on this system: {}", .0.join(", "))]
+    UnknownKeyNames(Vec<String>),
 }
 
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+impl ConfigFileError {
+    /// Writing failed because the user is not allowed to write the file
+    pub fn is_permission_denied(&self) -> bool {
+        matches!(self, Self::Io(e) if e.kind() == std::io::ErrorKind::PermissionDenied)
+    }
+}
+
+/// Directory the `save-config` helper from `data/` is installed to. Packages installing it
+/// elsewhere set `EVREMAP_GTK_LIBEXECDIR` at build time, and the path in the polkit policy has to
+/// match.
+const SAVE_HELPER_DIR: &str = match option_env!("EVREMAP_GTK_LIBEXECDIR") {
+    Some(dir) => dir,
+    None => "/usr/libexec/evremap-gtk",
+};
+
+/// pkexec exit code when the authentication dialog was dismissed
+const PKEXEC_DISMISSED: i32 = 126;
+/// pkexec exit code when the user failed to authenticate or isn't allowed to
+const PKEXEC_NOT_AUTHORIZED: i32 = 127;
+
+/// Layout of evremap's config. Fields are serialized in declaration order, which gives the
+/// plain values first and then the `[[dual_role]]` and `[[remap]]` tables, like in evremap's
+/// examples. Keys are written by their evdev names, like `KEY_CAPSLOCK`, which evremap expects.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct ConfigFile {
     #[serde(default)]
     pub device_name: Option<String>,
@@ -28,25 +75,606 @@ pub struct ConfigFile {
     pub remap: Vec<RemapConfig>,
 }
 
+/// Configs in files ending with `.json` are read and written as JSON, to exchange them with
+/// other tools. evremap itself only reads TOML.
+pub fn is_json_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
 impl ConfigFile {
+    /// Read the config as TOML, or as JSON if [`is_json_path`] says so
     pub fn read_from<P: AsRef<Path>>(path: P) -> Result<Self, ConfigFileError> {
+        if is_json_path(path.as_ref()) {
+            return Self::read_json(path);
+        }
+        Self::read_with_source(path).map(|(config, _)| config)
+    }
+
+    /// Read a config exported with [`ConfigFile::write_json`]. Remap comments aren't part of
+    /// the JSON form.
+    pub fn read_json<P: AsRef<Path>>(path: P) -> Result<Self, ConfigFileError> {
+        let contents = std::fs::read_to_string(path).map_err(ConfigFileError::Io)?;
+        serde_json::from_str(&contents).map_err(ConfigFileError::Json)
+    }
+
+    /// Write the config as pretty-printed JSON, with the same fields as the TOML form
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigFileError> {
+        std::fs::write(path, self.to_json()?).map_err(ConfigFileError::Io)
+    }
+
+    /// Contents of the file written by [`ConfigFile::write_json`]
+    pub fn to_json(&self) -> Result<String, ConfigFileError> {
+        let mut contents = serde_json::to_string_pretty(self).map_err(ConfigFileError::Json)?;
+        contents.push('\n');
+        Ok(contents)
+    }
+
+    /// Read the config and keep the file's formatting and comments to save it back later
+    pub fn read_with_source<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<(Self, ConfigSource), ConfigFileError> {
+        let contents = std::fs::read_to_string(path).map_err(ConfigFileError::Io)?;
+        let (config, document) = Self::from_toml(&contents)?;
+        let source = ConfigSource {
+            document,
+            config: config.clone(),
+        };
+        Ok((config, source))
+    }
+
+    /// Read the config like [`ConfigFile::read_with_source`], leaving out the key names that
+    /// fail it with [`ConfigFileError::UnknownKeyNames`]. Dual-role entries with an unknown
+    /// trigger key are left out entirely.
+    pub fn read_dropping_unknown_keys<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<(Self, ConfigSource), ConfigFileError> {
         let contents = std::fs::read_to_string(path).map_err(ConfigFileError::Io)?;
-        toml::from_str(&contents).map_err(ConfigFileError::TomlDeserialize)
+        let mut document: DocumentMut = contents.parse().map_err(ConfigFileError::TomlEdit)?;
+        drop_unknown_key_names(&mut document);
+        let (config, document) = Self::from_toml(&document.to_string())?;
+        let source = ConfigSource {
+            document,
+            config: config.clone(),
+        };
+        Ok((config, source))
     }
 
     pub fn save_to<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigFileError> {
-        let contents = toml::to_string_pretty(self).map_err(ConfigFileError::TomlSerialize)?;
+        self.save_to_preserving(path, None)
+    }
+
+    /// Save the config, keeping the comments and formatting of the file it was opened from
+    pub fn save_to_preserving<P: AsRef<Path>>(
+        &self,
+        path: P,
+        source: Option<&ConfigSource>,
+    ) -> Result<(), ConfigFileError> {
+        let contents = self.to_toml(source)?;
         std::fs::write(path, contents).map_err(ConfigFileError::Io)
     }
+
+    /// Parse a config, taking the comments of the remaps from the comment lines right above
+    /// their `[[remap]]` headers
+    fn from_toml(contents: &str) -> Result<(Self, DocumentMut), ConfigFileError> {
+        let mut config: ConfigFile = match toml::from_str(contents) {
+            Ok(config) => config,
+            Err(e) => {
+                // Configs from newer kernels may name keys this system doesn't have, tell which
+                // ones instead of the generic error about the first of them
+                let unknown = contents
+                    .parse::<DocumentMut>()
+                    .map(|document| unknown_key_names(&document))
+                    .unwrap_or_default();
+                return Err(if unknown.is_empty() {
+                    ConfigFileError::TomlDeserialize(e)
+                } else {
+                    ConfigFileError::UnknownKeyNames(unknown)
+                });
+            }
+        };
+        let document: DocumentMut = contents.parse().map_err(ConfigFileError::TomlEdit)?;
+        if let Some(tables) = document.get("remap").and_then(Item::as_array_of_tables)
+            && tables.len() == config.remap.len()
+        {
+            for (remap, table) in config.remap.iter_mut().zip(tables.iter()) {
+                remap.comment = table_comment(table);
+            }
+        }
+        Ok((config, document))
+    }
+
+    /// Serialize the config, into the document of the opened file if there is one. The result
+    /// is parsed back to make sure it's the same config, so that a broken file is never written.
+    pub fn to_toml(&self, source: Option<&ConfigSource>) -> Result<String, ConfigFileError> {
+        let generated = toml::to_string_pretty(self).map_err(ConfigFileError::TomlSerialize)?;
+        let generated: DocumentMut = generated.parse().map_err(ConfigFileError::TomlEdit)?;
+        let mut document = match source {
+            Some(source) => source.apply(self, &generated),
+            None => generated,
+        };
+        if let Some(tables) = document
+            .get_mut("remap")
+            .and_then(Item::as_array_of_tables_mut)
+        {
+            for (remap, table) in self.remap.iter().zip(tables.iter_mut()) {
+                set_table_comment(table, remap.comment.as_deref());
+            }
+        }
+        let contents = document.to_string();
+        match Self::from_toml(&contents) {
+            Ok((read_back, _)) if read_back == *self => Ok(contents),
+            _ => Err(ConfigFileError::RoundTrip),
+        }
+    }
+
+    /// Save the config to a file the user can't write, like the ones in `/etc`. The config is
+    /// written to a private temporary file, which is then copied into place by the `save-config`
+    /// helper run through pkexec, optionally backing up the existing file like
+    /// [`backup_existing`] does.
+    /// Blocks until the user is done with the authentication dialog.
+    pub fn save_to_privileged<P: AsRef<Path>>(
+        &self,
+        path: P,
+        source: Option<&ConfigSource>,
+        backup: bool,
+    ) -> Result<(), ConfigFileError> {
+        let helper = Path::new(SAVE_HELPER_DIR).join("save-config");
+        if !helper.exists() {
+            return Err(ConfigFileError::SaveHelperMissing(helper));
+        }
+        let tmp_path = privileged_save_tmp_path();
+        self.save_to_preserving(&tmp_path, source)?;
+        let mut command = Command::new("pkexec");
+        command.arg(&helper);
+        // The helper appends the same suffix
+        if backup {
+            command.arg("--backup");
+        }
+        let status = command.arg(&tmp_path).arg(path.as_ref()).status();
+        // Nothing to do about a leftover file in the runtime dir, it's cleared on logout
+        let _ = std::fs::remove_file(&tmp_path);
+        let status = status.map_err(ConfigFileError::PkexecSpawn)?;
+        match status.code() {
+            Some(0) => Ok(()),
+            Some(PKEXEC_DISMISSED | PKEXEC_NOT_AUTHORIZED) => {
+                Err(ConfigFileError::PkexecNotAuthorized)
+            }
+            _ => Err(ConfigFileError::PrivilegedCopy(status)),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Appended to the path of a config to get the path of its backup
+const BACKUP_SUFFIX: &str = ".bak";
+
+/// Copy an existing config to `<path>.bak` before it's overwritten, replacing an older backup.
+/// Returns the path of the backup if there was anything to back up.
+pub fn backup_existing(path: &Path) -> std::io::Result<Option<PathBuf>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(BACKUP_SUFFIX);
+    let backup = PathBuf::from(backup);
+    std::fs::copy(path, &backup)?;
+    Ok(Some(backup))
+}
+
+/// A config file as it was written, with comments and formatting
+#[derive(Debug, Clone)]
+pub struct ConfigSource {
+    document: DocumentMut,
+    /// What the document was parsed into, to find the entries that were left unchanged
+    config: ConfigFile,
+}
+
+impl ConfigSource {
+    /// Keys of the document the editor doesn't know about, like `remap.foo` for a key inside
+    /// `[[remap]]` entries. They are kept when saving unless their entry is removed.
+    pub fn unknown_keys(&self) -> Vec<String> {
+        let table = self.document.as_table();
+        let mut unknown: Vec<String> = table
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| !["device_name", "phys", "dual_role", "remap"].contains(key))
+            .map(str::to_owned)
+            .collect();
+        for (entry_key, known) in [
+            ("dual_role", &["input", "hold", "tap"][..]),
+            ("remap", &["input", "output"][..]),
+        ] {
+            let Some(entries) = table.get(entry_key).and_then(Item::as_array_of_tables) else {
+                continue;
+            };
+            for (key, _) in entries.iter().flat_map(Table::iter) {
+                if known.contains(&key) {
+                    continue;
+                }
+                let key = format!("{entry_key}.{key}");
+                if !unknown.contains(&key) {
+                    unknown.push(key);
+                }
+            }
+        }
+        unknown
+    }
+
+    /// Put the values of `config` into a copy of the document. Unchanged entries are kept as is,
+    /// entries edited in place keep their comments and new ones are formatted like `generated`.
+    fn apply(&self, config: &ConfigFile, generated: &DocumentMut) -> DocumentMut {
+        let mut document = self.document.clone();
+        let table = document.as_table_mut();
+        for key in ["device_name", "phys"] {
+            match generated.get(key).and_then(Item::as_value) {
+                Some(value) => update_value(table, key, value),
+                None => {
+                    table.remove(key);
+                }
+            }
+        }
+        merge_entries(
+            table,
+            "dual_role",
+            &self.config.dual_role,
+            &config.dual_role,
+            generated,
+        );
+        merge_entries(table, "remap", &self.config.remap, &config.remap, generated);
+        document
+    }
+}
+
+/// Replace the value under `key` if it changed, keeping its trailing comment
+fn update_value(table: &mut Table, key: &str, value: &Value) {
+    if let Some(old) = table.get_mut(key).and_then(Item::as_value_mut) {
+        if !same_value(old, value) {
+            let decor = old.decor().clone();
+            *old = value.clone();
+            *old.decor_mut() = decor;
+        }
+        return;
+    }
+    table.insert(key, Item::Value(value.clone()));
+}
+
+/// Compare values ignoring their formatting. Only strings and arrays appear in configs.
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.value() == b.value(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same_value(a, b))
+        }
+        _ => false,
+    }
+}
+
+/// Rebuild the array of tables under `key` for the `new` entries. Entries found unchanged in
+/// `old` keep their original table, an edited entry reuses the table at its position if that
+/// one wasn't kept elsewhere.
+fn merge_entries<T: PartialEq>(
+    table: &mut Table,
+    key: &str,
+    old: &[T],
+    new: &[T],
+    generated: &DocumentMut,
+) {
+    let Some(generated) = generated.get(key).and_then(Item::as_array_of_tables) else {
+        table.remove(key);
+        return;
+    };
+    let original = table
+        .get(key)
+        .and_then(Item::as_array_of_tables)
+        // The entries can't be told apart if they are written some other way, like inline
+        .filter(|original| original.len() == old.len())
+        .cloned()
+        .unwrap_or_else(ArrayOfTables::new);
+
+    let mut used = vec![false; original.len()];
+    let kept: Vec<Option<usize>> = new
+        .iter()
+        .map(|entry| {
+            let found = (0..original.len()).find(|&idx| !used[idx] && old[idx] == *entry);
+            if let Some(idx) = found {
+                used[idx] = true;
+            }
+            found
+        })
+        .collect();
+
+    let mut merged = ArrayOfTables::new();
+    for (idx, (kept, generated_table)) in kept.into_iter().zip(generated.iter()).enumerate() {
+        let entry_table = match kept {
+            Some(original_idx) => original.get(original_idx).cloned(),
+            None if used.get(idx) == Some(&false) => {
+                used[idx] = true;
+                original.get(idx).cloned().map(|mut edited| {
+                    for (entry_key, item) in generated_table.iter() {
+                        if let Some(value) = item.as_value() {
+                            update_value(&mut edited, entry_key, value);
+                        }
+                    }
+                    edited
+                })
+            }
+            None => None,
+        };
+        merged.push(entry_table.unwrap_or_else(|| detached(generated_table)));
+    }
+    table.insert(key, Item::ArrayOfTables(merged));
+}
+
+/// Fields of the entries that hold key names, by the name of their array of tables
+const KEY_FIELDS: [(&str, &[&str]); 2] = [
+    ("dual_role", &["input", "hold", "tap"]),
+    ("remap", &["input", "output"]),
+];
+
+/// The name is one evremap and the editor can read, like `KEY_A`
+fn is_known_key_name(name: &str) -> bool {
+    toml::Value::String(name.to_owned())
+        .try_into::<KeyCode>()
+        .is_ok()
+}
+
+/// Strings in the key fields of the entries that aren't known key names, each listed once
+fn unknown_key_names(document: &DocumentMut) -> Vec<String> {
+    let mut unknown: Vec<String> = Vec::new();
+    for (entry_key, fields) in KEY_FIELDS {
+        let Some(entries) = document.get(entry_key).and_then(Item::as_array_of_tables) else {
+            continue;
+        };
+        let values = entries
+            .iter()
+            .flat_map(|entry| fields.iter().filter_map(|field| entry.get(field)))
+            .filter_map(Item::as_value)
+            .flat_map(|value| match value.as_array() {
+                Some(array) => array.iter().collect(),
+                None => vec![value],
+            });
+        for name in values.filter_map(Value::as_str) {
+            if !is_known_key_name(name) && !unknown.iter().any(|n| n == name) {
+                unknown.push(name.to_owned());
+            }
+        }
+    }
+    unknown
+}
+
+/// Remove the names listed by [`unknown_key_names`] from the key lists. A dual-role entry can't
+/// do without its trigger key, so the entry is removed instead.
+fn drop_unknown_key_names(document: &mut DocumentMut) {
+    let is_unknown = |value: &Value| value.as_str().is_some_and(|name| !is_known_key_name(name));
+    if let Some(entries) = document
+        .get_mut("dual_role")
+        .and_then(Item::as_array_of_tables_mut)
+    {
+        entries.retain(|entry| {
+            !entry
+                .get("input")
+                .and_then(Item::as_value)
+                .is_some_and(is_unknown)
+        });
+    }
+    for (entry_key, fields) in KEY_FIELDS {
+        let Some(entries) = document
+            .get_mut(entry_key)
+            .and_then(Item::as_array_of_tables_mut)
+        else {
+            continue;
+        };
+        for entry in entries.iter_mut() {
+            for field in fields {
+                if let Some(array) = entry.get_mut(field).and_then(Item::as_array_mut) {
+                    array.retain(|value| !is_unknown(value));
+                }
+            }
+        }
+    }
+}
+
+/// Comment lines right above a table header, like `# Caps Lock as Escape`, joined into one line
+fn table_comment(table: &Table) -> Option<String> {
+    let prefix = table.decor().prefix()?.as_str()?;
+    let mut lines: Vec<&str> = prefix
+        .lines()
+        .rev()
+        .map(str::trim)
+        .take_while(|line| line.starts_with('#'))
+        // Only the first `#` is the comment marker, the comment itself may start with one too
+        .map(|line| line.strip_prefix('#').unwrap_or(line).trim())
+        .collect();
+    lines.reverse();
+    let comment = lines.join(" ");
+    (!comment.is_empty()).then_some(comment)
+}
+
+/// Replace the comment lines right above a table header, keeping what comes before them
+fn set_table_comment(table: &mut Table, comment: Option<&str>) {
+    if table_comment(table).as_deref() == comment {
+        return;
+    }
+    let prefix = table
+        .decor()
+        .prefix()
+        .and_then(|p| p.as_str())
+        .unwrap_or_default()
+        .to_owned();
+    let mut lines: Vec<&str> = prefix.lines().collect();
+    while lines
+        .last()
+        .is_some_and(|line| line.trim().starts_with('#'))
+    {
+        lines.pop();
+    }
+    let mut prefix: String = lines.iter().map(|line| format!("{line}\n")).collect();
+    if let Some(comment) = comment {
+        prefix += &format!("# {comment}\n");
+    }
+    table.decor_mut().set_prefix(prefix);
+}
+
+/// Copy of a table without its position in the document it came from, so that it's written
+/// after the tables around it
+fn detached(table: &Table) -> Table {
+    let mut copy = Table::new();
+    for (key, item) in table.iter() {
+        copy.insert(key, item.clone());
+    }
+    copy
+}
+
+/// Temporary file in the user's private runtime dir for [`ConfigFile::save_to_privileged`]
+fn privileged_save_tmp_path() -> PathBuf {
+    glib::user_runtime_dir().join(format!("evremap-gtk-save-{}.toml", std::process::id()))
+}
+
+/// Something in an opened config that evremap would reject or that is likely a mistake
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ConfigWarning {
+    #[error(
+        "remaps {} and {} have the same input {}",
+        .first + 1,
+        .second + 1,
+        summarize_keys(.input.iter().copied())
+    )]
+    DuplicateRemapInput {
+        first: usize,
+        second: usize,
+        input: Vec<KeyCode>,
+    },
+    #[error("dual-role entry {} ({}) has neither hold nor tap keys", .0 + 1, summarize_keys([*.1]))]
+    EmptyDualRole(usize, KeyCode),
+}
+
+/// Entries evremap would reject or ignore, found before saving
+#[derive(Debug, Default)]
+pub struct SaveProblems {
+    /// Indices of the remaps with problems
+    pub remaps: HashSet<usize>,
+    /// Indices of the dual-role entries with problems
+    pub dual_role: HashSet<usize>,
+    /// One line for each problem found
+    pub descriptions: Vec<String>,
+}
+
+impl SaveProblems {
+    pub fn is_empty(&self) -> bool {
+        self.descriptions.is_empty()
+    }
+}
+
+impl ConfigFile {
+    /// Check a config that was written elsewhere, like by hand or for an older evremap version.
+    /// Unknown key names don't get this far, they already fail parsing.
+    pub fn validate(&self) -> Vec<ConfigWarning> {
+        let mut warnings = Vec::new();
+        for (idx, remap) in self.remap.iter().enumerate() {
+            if let Some(first) = self.remap[..idx].iter().position(|o| o.same_input(remap)) {
+                warnings.push(ConfigWarning::DuplicateRemapInput {
+                    first,
+                    second: idx,
+                    input: remap.input.clone(),
+                });
+            }
+        }
+        for (idx, dual_role) in self.dual_role.iter().enumerate() {
+            if dual_role.hold.is_empty() && dual_role.tap.is_empty() {
+                warnings.push(ConfigWarning::EmptyDualRole(idx, dual_role.input));
+            }
+        }
+        warnings
+    }
+
+    /// Look for empty or invalid key lists, remaps that do nothing and duplicated entries. A
+    /// dual-role entry may leave either hold or tap empty, but not both.
+    pub fn save_problems(&self) -> SaveProblems {
+        let mut problems = SaveProblems::default();
+        for (idx, remap) in self.remap.iter().enumerate() {
+            let mut found = Vec::new();
+            if remap.input.is_empty() {
+                found.push("input is empty".to_owned());
+            }
+            if remap.output.is_empty() {
+                found.push("output is empty".to_owned());
+            }
+            if let Err(e) = validate_keys(&remap.input) {
+                found.push(format!("input: {e}"));
+            }
+            if let Err(e) = validate_keys(&remap.output) {
+                found.push(format!("output: {e}"));
+            }
+            if let Some(issue) = remap.check() {
+                found.push(issue.to_string());
+            }
+            if let Some(other) = self.remap[..idx].iter().position(|o| o.same_as(remap)) {
+                found.push(format!("duplicate of remap {}", other + 1));
+            }
+            for problem in found {
+                problems.remaps.insert(idx);
+                problems.descriptions.push(format!(
+                    "Remap {} ({}): {problem}",
+                    idx + 1,
+                    remap.summary()
+                ));
+            }
+        }
+        for (idx, dual_role) in self.dual_role.iter().enumerate() {
+            let mut found = Vec::new();
+            // Either one can be left empty to only act on a tap or on a hold
+            if dual_role.hold.is_empty() && dual_role.tap.is_empty() {
+                found.push("neither hold nor tap keys are set".to_owned());
+            }
+            if let Err(e) = validate_keys(&dual_role.hold) {
+                found.push(format!("hold keys: {e}"));
+            }
+            if let Err(e) = validate_keys(&dual_role.tap) {
+                found.push(format!("tap keys: {e}"));
+            }
+            if let Some(other) = self.dual_role[..idx]
+                .iter()
+                .position(|o| o.same_as(dual_role))
+            {
+                found.push(format!("duplicate of dual-role entry {}", other + 1));
+            }
+            for problem in found {
+                problems.dual_role.insert(idx);
+                problems.descriptions.push(format!(
+                    "Dual-role entry {} ({}): {problem}",
+                    idx + 1,
+                    dual_role.summary()
+                ));
+            }
+        }
+        problems
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct DualRoleConfig {
     pub input: KeyCode,
     pub hold: Vec<KeyCode>,
     pub tap: Vec<KeyCode>,
 }
 
+impl DualRoleConfig {
+    /// Whether both entries trigger on the same key and produce the same keys in the same order
+    pub fn same_as(&self, other: &DualRoleConfig) -> bool {
+        self.input == other.input && self.hold == other.hold && self.tap == other.tap
+    }
+
+    /// Friendly one-line summary, like "Capslock: tap Esc / hold Ctrl"
+    pub fn summary(&self) -> String {
+        format!(
+            "{}: tap {} / hold {}",
+            summarize_keys([self.input]),
+            summarize_keys(self.tap.iter().copied()),
+            summarize_keys(self.hold.iter().copied())
+        )
+    }
+}
+
 impl Default for DualRoleConfig {
     fn default() -> Self {
         Self {
@@ -57,8 +685,258 @@ impl Default for DualRoleConfig {
     }
 }
 
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct RemapConfig {
     pub input: Vec<KeyCode>,
     pub output: Vec<KeyCode>,
+    /// What the remap is for, written as a comment above it since evremap has no field for it
+    #[serde(skip)]
+    pub comment: Option<String>,
+}
+
+/// Suspicious remaps that are still allowed to be saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum RemapIssue {
+    #[error("Output is the same as the input, this remap does nothing")]
+    IdentityRemap,
+}
+
+impl RemapConfig {
+    pub fn check(&self) -> Option<RemapIssue> {
+        (!self.input.is_empty() && self.input == self.output).then_some(RemapIssue::IdentityRemap)
+    }
+
+    /// Whether both remaps are triggered by the same keys
+    pub fn same_input(&self, other: &RemapConfig) -> bool {
+        key_set(&self.input) == key_set(&other.input)
+    }
+
+    /// Whether both remaps have the same input keys and output the same keys in the same order
+    pub fn same_as(&self, other: &RemapConfig) -> bool {
+        self.same_input(other) && self.output == other.output
+    }
+
+    /// Friendly one-line summary, like "Ctrl+Shift+A → Esc"
+    pub fn summary(&self) -> String {
+        format!(
+            "{} → {}",
+            summarize_keys(self.input.iter().copied()),
+            summarize_keys(self.output.iter().copied())
+        )
+    }
+}
+
+/// Remap inputs are chords, evremap matches them whatever order the keys are pressed in. Outputs
+/// are emitted in order, so they are compared as they are.
+fn key_set(keys: &[KeyCode]) -> HashSet<KeyCode> {
+    keys.iter().copied().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output keys with modifiers mixed in between the other keys
+    const MIXED: [KeyCode; 4] = [
+        KeyCode::KEY_A,
+        KeyCode::KEY_LEFTCTRL,
+        KeyCode::KEY_B,
+        KeyCode::KEY_LEFTSHIFT,
+    ];
+
+    /// Path in the temporary directory that is unique to this test process
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("evremap-gtk-test-{}-{name}", std::process::id()))
+    }
+
+    fn sample_config() -> ConfigFile {
+        ConfigFile {
+            device_name: Some("AT Translated Set 2 keyboard".to_owned()),
+            phys: Some("isa0060/serio0/input0".to_owned()),
+            dual_role: vec![
+                DualRoleConfig {
+                    input: KeyCode::KEY_CAPSLOCK,
+                    hold: vec![KeyCode::KEY_LEFTCTRL],
+                    tap: vec![KeyCode::KEY_ESC],
+                },
+                DualRoleConfig {
+                    input: KeyCode::KEY_SPACE,
+                    hold: vec![KeyCode::KEY_LEFTSHIFT, KeyCode::KEY_LEFTALT],
+                    tap: vec![KeyCode::KEY_SPACE],
+                },
+            ],
+            remap: vec![
+                RemapConfig {
+                    input: vec![KeyCode::KEY_LEFTALT, KeyCode::KEY_H],
+                    output: vec![KeyCode::KEY_LEFT],
+                    comment: Some("Vim-style arrows".to_owned()),
+                },
+                RemapConfig {
+                    input: vec![KeyCode::KEY_RIGHTALT],
+                    output: vec![
+                        KeyCode::KEY_LEFTCTRL,
+                        KeyCode::KEY_LEFTSHIFT,
+                        KeyCode::KEY_U,
+                    ],
+                    comment: None,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn written_config_reads_back_the_same() {
+        let config = sample_config();
+        let path = temp_path("round-trip.toml");
+        std::fs::write(&path, config.to_toml(None).unwrap()).unwrap();
+        let read_back = ConfigFile::read_from(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(read_back.unwrap(), config);
+    }
+
+    #[test]
+    fn empty_config_reads_back_the_same() {
+        let config = ConfigFile::default();
+        let path = temp_path("empty.toml");
+        std::fs::write(&path, config.to_toml(None).unwrap()).unwrap();
+        let read_back = ConfigFile::read_from(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(read_back.unwrap(), config);
+    }
+
+    #[test]
+    fn config_converted_to_json_reads_back_the_same() {
+        let toml_path = temp_path("to-json.toml");
+        let json_path = temp_path("to-json.json");
+        std::fs::write(&toml_path, sample_config().to_toml(None).unwrap()).unwrap();
+        let config = ConfigFile::read_from(&toml_path).unwrap();
+        config.write_json(&json_path).unwrap();
+        let read_back = ConfigFile::read_json(&json_path);
+        let _ = std::fs::remove_file(&toml_path);
+        let _ = std::fs::remove_file(&json_path);
+
+        // Comments aren't part of the JSON form
+        let mut expected = config;
+        for remap in &mut expected.remap {
+            remap.comment = None;
+        }
+        assert_eq!(read_back.unwrap(), expected);
+    }
+
+    #[test]
+    fn json_paths_are_read_as_json() {
+        let config = sample_config();
+        let path = temp_path("by-extension.JSON");
+        std::fs::write(&path, config.to_json().unwrap()).unwrap();
+        let read_back = ConfigFile::read_from(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(read_back.unwrap().dual_role, config.dual_role);
+    }
+
+    #[test]
+    fn edited_config_keeps_the_comments_of_the_file() {
+        let contents = r#"# Laptop keyboard
+device_name = "AT Translated Set 2 keyboard"
+
+[[dual_role]]
+input = "KEY_CAPSLOCK"
+hold = ["KEY_LEFTCTRL"]
+tap = ["KEY_ESC"]
+
+# Vim-style
+# arrows
+[[remap]]
+input = ["KEY_LEFTALT", "KEY_H"]
+output = ["KEY_LEFT"]
+"#;
+        let path = temp_path("commented.toml");
+        std::fs::write(&path, contents).unwrap();
+        let (mut config, source) = ConfigFile::read_with_source(&path).unwrap();
+        assert_eq!(config.remap[0].comment.as_deref(), Some("Vim-style arrows"));
+
+        config.dual_role[0].tap = vec![KeyCode::KEY_ENTER];
+        config.remap.push(RemapConfig {
+            input: vec![KeyCode::KEY_LEFTALT, KeyCode::KEY_L],
+            output: vec![KeyCode::KEY_RIGHT],
+            comment: Some("Added later".to_owned()),
+        });
+        let saved = config.to_toml(Some(&source)).unwrap();
+        std::fs::write(&path, &saved).unwrap();
+        let read_back = ConfigFile::read_from(&path);
+        let _ = std::fs::remove_file(&path);
+
+        assert!(saved.starts_with("# Laptop keyboard\n"));
+        assert_eq!(read_back.unwrap(), config);
+    }
+
+    #[test]
+    fn dual_role_needs_only_hold_or_tap() {
+        let mut config = sample_config();
+        config.dual_role[0].hold.clear();
+        config.dual_role[1].tap.clear();
+        assert!(config.save_problems().is_empty());
+
+        config.dual_role[0].tap.clear();
+        let problems = config.save_problems();
+        assert_eq!(problems.dual_role, HashSet::from([0]));
+        assert!(problems.remaps.is_empty());
+    }
+
+    #[test]
+    fn placeholder_keys_are_save_problems() {
+        let mut config = sample_config();
+        config.remap[1].output.push(KeyCode::KEY_RESERVED);
+        config.dual_role[1].hold = vec![KeyCode::KEY_UNKNOWN];
+        let problems = config.save_problems();
+        assert_eq!(problems.remaps, HashSet::from([1]));
+        assert_eq!(problems.dual_role, HashSet::from([1]));
+    }
+
+    #[test]
+    fn comments_starting_with_a_hash_read_back_the_same() {
+        let mut config = sample_config();
+        config.remap[0].comment = Some("#vim".to_owned());
+        config.remap[1].comment = Some("#".to_owned());
+        let saved = config.to_toml(None).unwrap();
+        let path = temp_path("hash-comments.toml");
+        std::fs::write(&path, &saved).unwrap();
+        let read_back = ConfigFile::read_from(&path);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(read_back.unwrap(), config);
+    }
+
+    #[test]
+    fn mixed_modifier_order_survives_round_trip() {
+        let config = ConfigFile {
+            device_name: Some("Keyboard".to_owned()),
+            remap: vec![RemapConfig {
+                input: vec![KeyCode::KEY_CAPSLOCK],
+                output: MIXED.to_vec(),
+                comment: None,
+            }],
+            ..Default::default()
+        };
+        let contents = config.to_toml(None).unwrap();
+        let read_back: ConfigFile = toml::from_str(&contents).unwrap();
+        assert_eq!(read_back.remap[0].output, MIXED);
+        assert_eq!(read_back, config);
+    }
+
+    #[test]
+    fn outputs_differing_in_order_are_different() {
+        let remap = RemapConfig {
+            input: vec![KeyCode::KEY_LEFTCTRL, KeyCode::KEY_A],
+            output: MIXED.to_vec(),
+            comment: None,
+        };
+        let mut reordered = remap.clone();
+        reordered.output.reverse();
+        assert!(!remap.same_as(&reordered));
+
+        // The input is a chord, its order doesn't matter
+        let mut swapped_input = remap.clone();
+        swapped_input.input.reverse();
+        assert!(remap.same_as(&swapped_input));
+    }
 }
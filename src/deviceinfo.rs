@@ -1,47 +1,380 @@
 // This file is loosely copied from [`evremap`](https://github.com/wez/evremap/blob/master/src/deviceinfo.rs)
 use std::{
     cmp::Ordering,
+    io::ErrorKind,
+    os::fd::AsRawFd,
     path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, Instant},
 };
 
-use evdev_rs::{Device, DeviceWrapper, enums::EventType};
+use evdev_rs::{
+    Device, DeviceWrapper,
+    enums::{EV_KEY, EventCode, EventType},
+};
 use thiserror::Error;
 
+use crate::evdev_utils::{KeyCode, list_keycodes};
+
+/// How long udev has to stay quiet after an input device is added or removed before the change is
+/// reported. A device with several event nodes produces a burst of events.
+const HOTPLUG_SETTLE_DELAY: Duration = Duration::from_millis(500);
+/// How often the hotplug watch checks whether it should stop while nothing is plugged in
+const HOTPLUG_STOP_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+const DEFAULT_INPUT_DIR: &str = "/dev/input";
+/// Environment variable overriding [`DEFAULT_INPUT_DIR`], for containers and fake device trees
+const INPUT_DIR_ENV: &str = "EVREMAP_GTK_INPUT_DIR";
+
+/// Directory the event device nodes are looked up in
+pub fn input_dir() -> PathBuf {
+    std::env::var_os(INPUT_DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| PathBuf::from(DEFAULT_INPUT_DIR), PathBuf::from)
+}
+
 #[derive(Debug, Clone)]
 pub struct DeviceInfo {
     pub name: String,
     pub phys: Option<String>,
     pub path: PathBuf,
     pub supports_remap: bool,
+    pub is_keyboard: bool,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// One of the `BUS_*` constants from `linux/input.h`
+    pub bus_type: u16,
+    /// Created by software through uinput, like the output device of a running evremap
+    pub is_virtual: bool,
+    /// Keys the device reports it can emit
+    pub keys: Vec<KeyCode>,
+    /// User-assigned name from the settings, purely cosmetic
+    pub alias: Option<String>,
+}
+
+/// Identifies a device across reconnects and reboots, unlike its path
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceId {
+    pub name: String,
+    pub phys: Option<String>,
+}
+
+impl DeviceId {
+    pub fn of(device: &DeviceInfo) -> Self {
+        Self {
+            name: device.name.clone(),
+            phys: device.phys.clone(),
+        }
+    }
+
+    pub fn matches(&self, device: &DeviceInfo) -> bool {
+        self.name == device.name && self.phys == device.phys
+    }
+}
+
+/// What a device can do with keys, from the events it reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeyCapability {
+    /// No key or button events, nothing evremap can remap
+    None,
+    /// Keys or buttons, but not the letters of a keyboard, like a mouse or a media remote
+    Keys,
+    /// Letter keys, a keyboard or something pretending to be one
+    Keyboard,
+}
+
+impl KeyCapability {
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyCapability::None => "No keys",
+            KeyCapability::Keys => "Keys or buttons",
+            KeyCapability::Keyboard => "Keyboard",
+        }
+    }
+}
+
+/// Which devices the browser lists, by their [`KeyCapability`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeviceKindFilter {
+    All,
+    /// Devices evremap can remap
+    #[default]
+    HasKeys,
+    KeyboardLike,
+}
+
+impl DeviceKindFilter {
+    pub const ALL: [DeviceKindFilter; 3] = [
+        DeviceKindFilter::All,
+        DeviceKindFilter::HasKeys,
+        DeviceKindFilter::KeyboardLike,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeviceKindFilter::All => "All devices",
+            DeviceKindFilter::HasKeys => "Devices with keys",
+            DeviceKindFilter::KeyboardLike => "Keyboard-like devices",
+        }
+    }
+
+    pub fn matches(&self, device: &DeviceInfo) -> bool {
+        let capability = device.key_capability();
+        match self {
+            DeviceKindFilter::All => true,
+            DeviceKindFilter::HasKeys => capability >= KeyCapability::Keys,
+            DeviceKindFilter::KeyboardLike => capability == KeyCapability::Keyboard,
+        }
+    }
+}
+
+/// Criteria the device list can be ordered by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeviceSort {
+    #[default]
+    Name,
+    Path,
+    /// Devices evremap can remap before the rest, then by name
+    SupportsRemapFirst,
+}
+
+impl DeviceSort {
+    pub const ALL: [DeviceSort; 3] = [
+        DeviceSort::Name,
+        DeviceSort::Path,
+        DeviceSort::SupportsRemapFirst,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeviceSort::Name => "Name",
+            DeviceSort::Path => "Path",
+            DeviceSort::SupportsRemapFirst => "Supports remap first",
+        }
+    }
+
+    pub fn sort(&self, devices: &mut [DeviceInfo]) {
+        match self {
+            // Order by name, but when multiple devices have the same name,
+            // order by the event device unit number
+            DeviceSort::Name => devices.sort_by(cmp_by_name),
+            DeviceSort::Path => devices.sort_by_key(|d| event_number_from_path(&d.path)),
+            DeviceSort::SupportsRemapFirst => devices.sort_by(|a, b| {
+                b.supports_remap
+                    .cmp(&a.supports_remap)
+                    .then_with(|| cmp_by_name(a, b))
+            }),
+        }
+    }
+}
+
+/// Identifying property of a device that can be copied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceField {
+    Name,
+    Phys,
+    Path,
+}
+
+impl DeviceField {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeviceField::Name => "name",
+            DeviceField::Phys => "phys",
+            DeviceField::Path => "path",
+        }
+    }
+}
+
+/// Result of scanning the [`input_dir`]
+#[derive(Debug)]
+pub struct DeviceScan {
+    pub devices: Vec<DeviceInfo>,
+    /// Event devices that exist but couldn't be opened or read
+    pub skipped: Vec<DeviceInfoError>,
+}
+
+impl DeviceScan {
+    /// Short status line, like "Found 18 devices (3 skipped)"
+    pub fn summary(&self) -> String {
+        let found = match self.devices.len() {
+            1 => "Found 1 device".to_owned(),
+            n => format!("Found {n} devices"),
+        };
+        if self.skipped.is_empty() {
+            found
+        } else {
+            format!("{found} ({} skipped)", self.skipped.len())
+        }
+    }
+
+    /// How many devices were skipped because the user isn't allowed to open them
+    pub fn permission_denied_count(&self) -> usize {
+        self.skipped
+            .iter()
+            .filter(|e| matches!(e, DeviceInfoError::PermissionDenied(_)))
+            .count()
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum DeviceInfoError {
     #[error("Error opening file at {0:?}: {1}")]
-    FileOpen(PathBuf, std::io::Error),
+    FileOpen(PathBuf, #[source] std::io::Error),
+    #[error("Not allowed to open {0:?}")]
+    PermissionDenied(PathBuf),
     #[error("Filesystem error: {0}")]
-    Io(std::io::Error),
+    Io(#[source] std::io::Error),
     #[error("evdev error: {0}")]
-    Evdev(std::io::Error),
+    Evdev(#[source] std::io::Error),
+    #[error("udev error: {0}")]
+    Udev(#[source] std::io::Error),
 }
 
 impl DeviceInfo {
+    pub fn key_capability(&self) -> KeyCapability {
+        match (self.supports_remap, self.is_keyboard) {
+            (false, _) => KeyCapability::None,
+            (true, false) => KeyCapability::Keys,
+            (true, true) => KeyCapability::Keyboard,
+        }
+    }
+
+    /// [`None`] for a missing phys
+    pub fn field(&self, field: DeviceField) -> Option<String> {
+        match field {
+            DeviceField::Name => Some(self.name.clone()),
+            DeviceField::Phys => self.phys.clone(),
+            DeviceField::Path => Some(self.path.display().to_string()),
+        }
+    }
+
     pub fn with_path(path: PathBuf) -> Result<Self, DeviceInfoError> {
-        let f =
-            std::fs::File::open(&path).map_err(|e| DeviceInfoError::FileOpen(path.clone(), e))?;
+        let f = std::fs::File::open(&path).map_err(|e| match e.kind() {
+            ErrorKind::PermissionDenied => DeviceInfoError::PermissionDenied(path.clone()),
+            _ => DeviceInfoError::FileOpen(path.clone(), e),
+        })?;
         let input = Device::new_from_file(f).map_err(DeviceInfoError::Evdev)?;
+        let name = input.name().unwrap_or("").to_string();
 
         Ok(Self {
-            name: input.name().unwrap_or("").to_string(),
+            is_virtual: name.starts_with("evremap ") || is_virtual_device_path(&path),
+            name,
             phys: input.phys().map(|s| s.to_owned()),
             path,
             supports_remap: input.has_event_type(&EventType::EV_KEY),
+            // Anything that can type letters is considered a keyboard
+            is_keyboard: input.has_event_code(&EventCode::EV_KEY(EV_KEY::KEY_A))
+                && input.has_event_code(&EventCode::EV_KEY(EV_KEY::KEY_Z)),
+            vendor_id: input.vendor_id(),
+            product_id: input.product_id(),
+            bus_type: input.bustype(),
+            keys: list_keycodes()
+                .iter()
+                .copied()
+                .filter(|k| input.has_event_code(&EventCode::EV_KEY(*k)))
+                .collect(),
+            alias: None,
         })
     }
 
-    pub fn obtain_device_list() -> Result<Vec<DeviceInfo>, DeviceInfoError> {
+    /// Vendor and product IDs in the usual hex notation, like `046d:c52b`
+    pub fn vid_pid(&self) -> String {
+        format!("{:04x}:{:04x}", self.vendor_id, self.product_id)
+    }
+
+    /// Name of the bus the device is connected through, or its number if it's an unusual one
+    pub fn bus_name(&self) -> String {
+        let name = match self.bus_type {
+            0x01 => "PCI",
+            0x03 => "USB",
+            0x05 => "Bluetooth",
+            0x06 => "Virtual",
+            0x10 => "ISA",
+            0x11 => "PS/2",
+            0x18 => "I2C",
+            0x19 => "Host",
+            0x1C => "SPI",
+            other => return format!("Bus {other:#04x}"),
+        };
+        name.to_owned()
+    }
+
+    /// The alias if the user has assigned one, the device name otherwise
+    pub fn display_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Call `on_change` whenever udev reports an event device being added or removed, until it
+    /// returns `false` or the sender of `stop` is dropped. A burst of changes, like a device with
+    /// several event nodes being plugged in, results in a single call once things settle down.
+    ///
+    /// udev only knows about the real devices, so changes under an [`input_dir`] override aren't
+    /// noticed.
+    pub fn watch_hotplug(
+        stop: mpsc::Receiver<()>,
+        mut on_change: impl FnMut() -> bool,
+    ) -> Result<(), DeviceInfoError> {
+        let socket = udev::MonitorBuilder::new()
+            .and_then(|builder| builder.match_subsystem("input"))
+            .and_then(|builder| builder.listen())
+            .map_err(DeviceInfoError::Udev)?;
+        let mut settle_deadline: Option<Instant> = None;
+        loop {
+            if !matches!(stop.try_recv(), Err(mpsc::TryRecvError::Empty)) {
+                return Ok(());
+            }
+            let timeout = settle_deadline.map_or(HOTPLUG_STOP_CHECK_INTERVAL, |deadline| {
+                deadline
+                    .saturating_duration_since(Instant::now())
+                    .min(HOTPLUG_STOP_CHECK_INTERVAL)
+            });
+            let mut poll_fd = libc::pollfd {
+                fd: socket.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: the pointer is to a single pollfd that outlives the call
+            let ready = unsafe { libc::poll(&mut poll_fd, 1, timeout.as_millis() as libc::c_int) };
+            if ready < 0 {
+                let err = std::io::Error::last_os_error();
+                if err.kind() == ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(DeviceInfoError::Udev(err));
+            }
+
+            let mut changed = false;
+            // Read all the pending events, the socket doesn't block
+            for event in socket.iter() {
+                let is_event_node = event
+                    .sysname()
+                    .to_str()
+                    .is_some_and(|name| name.starts_with("event"));
+                if is_event_node
+                    && matches!(
+                        event.event_type(),
+                        udev::EventType::Add | udev::EventType::Remove
+                    )
+                {
+                    changed = true;
+                }
+            }
+            if changed {
+                settle_deadline = Some(Instant::now() + HOTPLUG_SETTLE_DELAY);
+            } else if settle_deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                settle_deadline = None;
+                if !on_change() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    pub fn obtain_device_list() -> Result<DeviceScan, DeviceInfoError> {
         let mut devices = vec![];
-        for entry in std::fs::read_dir("/dev/input").map_err(DeviceInfoError::Io)? {
+        let mut skipped = vec![];
+        for entry in std::fs::read_dir(input_dir()).map_err(DeviceInfoError::Io)? {
             let entry = entry.map_err(DeviceInfoError::Io)?;
 
             if !entry
@@ -59,22 +392,37 @@ impl DeviceInfo {
 
             match DeviceInfo::with_path(path) {
                 Ok(item) => devices.push(item),
-                Err(err) => log::error!("{:#}", err),
+                Err(err) => {
+                    log::error!("{:#}", err);
+                    skipped.push(err);
+                }
             }
         }
 
-        // Order by name, but when multiple devices have the same name,
-        // order by the event device unit number
-        devices.sort_by(|a, b| match a.name.cmp(&b.name) {
-            Ordering::Equal => {
-                event_number_from_path(&a.path).cmp(&event_number_from_path(&b.path))
-            }
-            different => different,
-        });
-        Ok(devices)
+        DeviceSort::Name.sort(&mut devices);
+        Ok(DeviceScan { devices, skipped })
     }
 }
 
+/// Check whether the device node belongs to a device that sysfs lists under `devices/virtual`,
+/// which is where uinput devices end up
+fn is_virtual_device_path(path: &Path) -> bool {
+    let Some(node) = path.file_name() else {
+        return false;
+    };
+    let sys_path = Path::new("/sys/class/input").join(node).join("device");
+    std::fs::canonicalize(sys_path)
+        .is_ok_and(|p| p.components().any(|c| c.as_os_str() == "virtual"))
+}
+
+/// Order by name, but when multiple devices have the same name, order by the event device unit
+/// number
+fn cmp_by_name(a: &DeviceInfo, b: &DeviceInfo) -> Ordering {
+    a.name
+        .cmp(&b.name)
+        .then_with(|| event_number_from_path(&a.path).cmp(&event_number_from_path(&b.path)))
+}
+
 fn event_number_from_path(path: &Path) -> u32 {
     match path.to_str() {
         Some(s) => match s.rfind("event") {
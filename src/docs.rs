@@ -0,0 +1,19 @@
+//! Links to the evremap documentation
+
+/// Part of the evremap documentation that can be opened from the app
+#[derive(Debug, Clone, Copy)]
+pub enum DocsTopic {
+    ConfigReference,
+    Remap,
+    DualRole,
+}
+
+impl DocsTopic {
+    pub fn url(&self) -> &'static str {
+        match self {
+            DocsTopic::ConfigReference => "https://github.com/wez/evremap#configuration",
+            DocsTopic::Remap => "https://github.com/wez/evremap#remap",
+            DocsTopic::DualRole => "https://github.com/wez/evremap#dual_role",
+        }
+    }
+}
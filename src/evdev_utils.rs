@@ -1,7 +1,12 @@
-use std::sync::OnceLock;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
 
 pub use evdev_rs::enums::EV_KEY as KeyCode;
-use evdev_rs::enums::EventCode;
+use evdev_rs::enums::{EventCode, int_to_ev_key};
+
+use crate::key_combo::is_modifier;
 
 pub fn list_keycodes() -> &'static [KeyCode] {
     static KEYS_ONCE_CELL: OnceLock<Vec<KeyCode>> = OnceLock::new();
@@ -22,8 +27,321 @@ pub fn list_keycodes() -> &'static [KeyCode] {
         .as_slice()
 }
 
+/// Kinds of `EV_KEY` codes the key dropdowns can be narrowed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyCategory {
+    #[default]
+    All,
+    Keys,
+    /// Buttons of mice, joysticks and the like
+    Buttons,
+}
+
+impl KeyCategory {
+    pub const ALL: [KeyCategory; 3] = [KeyCategory::All, KeyCategory::Keys, KeyCategory::Buttons];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyCategory::All => "All",
+            KeyCategory::Keys => "Keys",
+            KeyCategory::Buttons => "Buttons",
+        }
+    }
+
+    pub fn contains(&self, key: KeyCode) -> bool {
+        match self {
+            KeyCategory::All => true,
+            KeyCategory::Keys => !is_button(key),
+            KeyCategory::Buttons => is_button(key),
+        }
+    }
+}
+
+/// Buttons are named `BTN_*`, like `BTN_SIDE`, while keyboard keys are `KEY_*`
+pub fn is_button(key: KeyCode) -> bool {
+    EventCode::EV_KEY(key).to_string().starts_with("BTN_")
+}
+
+/// Sections of the key dropdowns, in the order they are listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum KeyGroup {
+    Letters,
+    Numbers,
+    Function,
+    Modifiers,
+    Media,
+    Numpad,
+    Buttons,
+    Other,
+}
+
+impl KeyGroup {
+    /// Classify a key by its name, `KEY_KP*` keys are numpad keys, `KEY_F1` to `KEY_F24` are
+    /// function keys and so on
+    pub fn of(key: KeyCode) -> Self {
+        // Listed exactly, parts like "PLAY" or "PAUSE" also appear in KEY_DISPLAY_OFF or in the
+        // Pause/Break key of keyboards
+        const MEDIA_KEYS: [&str; 19] = [
+            "VOLUMEUP",
+            "VOLUMEDOWN",
+            "MUTE",
+            "MICMUTE",
+            "PLAY",
+            "PLAYPAUSE",
+            "PLAYCD",
+            "PAUSECD",
+            "STOPCD",
+            "NEXTSONG",
+            "PREVIOUSSONG",
+            "RECORD",
+            "REWIND",
+            "FASTFORWARD",
+            "MEDIA",
+            "MEDIA_REPEAT",
+            "EJECTCD",
+            "EJECTCLOSECD",
+            "CLOSECD",
+        ];
+
+        if is_modifier(&key) {
+            return KeyGroup::Modifiers;
+        }
+        let name = EventCode::EV_KEY(key).to_string();
+        if name.starts_with("BTN_") {
+            return KeyGroup::Buttons;
+        }
+        let Some(name) = name.strip_prefix("KEY_") else {
+            return KeyGroup::Other;
+        };
+        let is_single = |pred: fn(&char) -> bool| name.len() == 1 && name.chars().all(|c| pred(&c));
+        if is_single(char::is_ascii_uppercase) {
+            KeyGroup::Letters
+        } else if is_single(char::is_ascii_digit) {
+            KeyGroup::Numbers
+        } else if name
+            .strip_prefix('F')
+            .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        {
+            KeyGroup::Function
+        } else if name.starts_with("KP") {
+            KeyGroup::Numpad
+        } else if MEDIA_KEYS.contains(&name) {
+            KeyGroup::Media
+        } else {
+            KeyGroup::Other
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyGroup::Letters => "Letters",
+            KeyGroup::Numbers => "Numbers",
+            KeyGroup::Function => "Function",
+            KeyGroup::Modifiers => "Modifiers",
+            KeyGroup::Media => "Media",
+            KeyGroup::Numpad => "Numpad",
+            KeyGroup::Buttons => "Buttons",
+            KeyGroup::Other => "Other",
+        }
+    }
+}
+
+/// Order keys by [`KeyGroup`], keeping their order within each group
+pub fn sort_by_group(keys: &mut [KeyCode]) {
+    keys.sort_by_cached_key(|k| KeyGroup::of(*k));
+}
+
 pub fn list_keynames_iter() -> impl Iterator<Item = String> {
     list_keycodes()
         .iter()
         .map(|k| format!("{}", EventCode::EV_KEY(*k)))
 }
+
+/// Hint text for the keys that are among `unseen`, the keys the config's device supports but that
+/// were never observed from it while logging it, if there are any
+pub fn unseen_keys_hint(
+    keys: impl IntoIterator<Item = KeyCode>,
+    unseen: Option<&HashSet<KeyCode>>,
+) -> Option<String> {
+    let unseen_keys = unseen?;
+    let unseen: Vec<KeyCode> = keys
+        .into_iter()
+        .filter(|k| unseen_keys.contains(k))
+        .collect();
+    (!unseen.is_empty()).then(|| {
+        format!(
+            "Supported by the device but never seen from it while logging it: {}",
+            unseen
+                .into_iter()
+                .map(friendly_key_name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
+
+/// Translate a hardware keycode reported by GDK into the evdev key. On Linux these are evdev
+/// codes offset by 8, a leftover from X11.
+pub fn key_from_hardware_keycode(keycode: u32) -> Option<KeyCode> {
+    keycode.checked_sub(8).and_then(int_to_ev_key)
+}
+
+/// Find a key by its name, ignoring case and the `KEY_` prefix, so `rightalt`, `KEY_RIGHTALT`
+/// and `key_rightalt` all give the same key. The reverse of [`list_keynames_iter`].
+pub fn keycode_from_name(name: &str) -> Option<KeyCode> {
+    static KEYS_BY_NAME: OnceLock<HashMap<String, KeyCode>> = OnceLock::new();
+
+    let keys_by_name = KEYS_BY_NAME.get_or_init(|| {
+        list_keynames_iter()
+            .zip(list_keycodes().iter().copied())
+            .collect()
+    });
+    let name = name.trim().to_ascii_uppercase();
+    keys_by_name
+        .get(&name)
+        .or_else(|| keys_by_name.get(&format!("KEY_{name}")))
+        .copied()
+}
+
+/// Find the keys in a pasted list like `["KEY_CAPSLOCK", "KEY_LEFTCTRL"]` or `Ctrl+C`. Names
+/// can be separated by commas, whitespace or `+` and quoted. Returns the keys in order and the
+/// names that are not known.
+pub fn parse_key_list(text: &str) -> (Vec<KeyCode>, Vec<String>) {
+    let mut keys = Vec::new();
+    let mut unknown = Vec::new();
+    let names = text
+        .split(|c: char| c.is_whitespace() || matches!(c, ',' | '+' | '[' | ']' | '"' | '\''))
+        .filter(|name| !name.is_empty());
+    for name in names {
+        match keycode_from_name(name) {
+            Some(key) => keys.push(key),
+            None => unknown.push(name.to_owned()),
+        }
+    }
+    (keys, unknown)
+}
+
+/// Key name for displaying, with the numeric evdev code if `show_code` is set, like `KEY_A (30)`.
+/// Codes can be turned off in the preferences.
+pub fn key_label(key: KeyCode, show_code: bool) -> String {
+    let name = EventCode::EV_KEY(key).to_string();
+    if show_code {
+        format!("{name} ({})", key as u32)
+    } else {
+        name
+    }
+}
+
+/// Short human-readable key name, like `Ctrl` for `KEY_LEFTCTRL` or `Esc` for `KEY_ESC`
+pub fn friendly_key_name(key: KeyCode) -> String {
+    let name = match key {
+        KeyCode::KEY_LEFTCTRL => "Ctrl",
+        KeyCode::KEY_RIGHTCTRL => "RightCtrl",
+        KeyCode::KEY_LEFTSHIFT => "Shift",
+        KeyCode::KEY_RIGHTSHIFT => "RightShift",
+        KeyCode::KEY_LEFTALT => "Alt",
+        KeyCode::KEY_RIGHTALT => "AltGr",
+        KeyCode::KEY_LEFTMETA => "Super",
+        KeyCode::KEY_RIGHTMETA => "RightSuper",
+        KeyCode::KEY_FN => "Fn",
+        KeyCode::KEY_ESC => "Esc",
+        _ => {
+            let raw = EventCode::EV_KEY(key).to_string();
+            let raw = raw.strip_prefix("KEY_").unwrap_or(&raw);
+            let mut chars = raw.chars();
+            return match chars.next() {
+                Some(first) => first.to_string() + &chars.as_str().to_ascii_lowercase(),
+                None => raw.to_owned(),
+            };
+        }
+    };
+    name.to_owned()
+}
+
+/// Friendly one-line form of a key sequence in the order it's stored, like `Ctrl+Shift+A`
+pub fn summarize_keys(keys: impl IntoIterator<Item = KeyCode>) -> String {
+    let names: Vec<String> = keys.into_iter().map(friendly_key_name).collect();
+    if names.is_empty() {
+        "nothing".to_owned()
+    } else {
+        names.join("+")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_listed_name_resolves_to_its_key() {
+        for (name, key) in list_keynames_iter().zip(list_keycodes().iter().copied()) {
+            assert_eq!(keycode_from_name(&name), Some(key), "{name}");
+        }
+    }
+
+    #[test]
+    fn unknown_names_resolve_to_nothing() {
+        for name in ["", "   ", "KEY_", "KEY_NOT_A_KEY", "CAPS LOCK", "30"] {
+            assert_eq!(keycode_from_name(name), None, "{name:?}");
+        }
+    }
+
+    #[test]
+    fn names_are_case_insensitive() {
+        assert_eq!(
+            keycode_from_name("key_capslock"),
+            Some(KeyCode::KEY_CAPSLOCK)
+        );
+        assert_eq!(
+            keycode_from_name("Key_CapsLock"),
+            Some(KeyCode::KEY_CAPSLOCK)
+        );
+        assert_eq!(keycode_from_name("btn_left"), Some(KeyCode::BTN_LEFT));
+    }
+
+    #[test]
+    fn summaries_use_friendly_names_in_stored_order() {
+        assert_eq!(
+            summarize_keys([KeyCode::KEY_A, KeyCode::KEY_LEFTCTRL, KeyCode::KEY_ESC]),
+            "A+Ctrl+Esc"
+        );
+        assert_eq!(summarize_keys([KeyCode::KEY_CAPSLOCK]), "Capslock");
+        assert_eq!(summarize_keys([]), "nothing");
+    }
+
+    #[test]
+    fn key_prefix_can_be_left_out() {
+        assert_eq!(keycode_from_name("CAPSLOCK"), Some(KeyCode::KEY_CAPSLOCK));
+        assert_eq!(keycode_from_name(" esc "), Some(KeyCode::KEY_ESC));
+        assert_eq!(keycode_from_name("leftctrl"), Some(KeyCode::KEY_LEFTCTRL));
+    }
+
+    #[test]
+    fn keys_are_grouped_by_name() {
+        let cases = [
+            (KeyCode::KEY_A, KeyGroup::Letters),
+            (KeyCode::KEY_1, KeyGroup::Numbers),
+            (KeyCode::KEY_F12, KeyGroup::Function),
+            (KeyCode::KEY_LEFTCTRL, KeyGroup::Modifiers),
+            (KeyCode::KEY_VOLUMEUP, KeyGroup::Media),
+            (KeyCode::KEY_PLAYPAUSE, KeyGroup::Media),
+            (KeyCode::KEY_KP5, KeyGroup::Numpad),
+            (KeyCode::BTN_LEFT, KeyGroup::Buttons),
+            (KeyCode::KEY_ESC, KeyGroup::Other),
+        ];
+        for (key, group) in cases {
+            assert_eq!(KeyGroup::of(key), group, "{key:?}");
+        }
+    }
+
+    #[test]
+    fn keys_named_like_media_keys_are_not_media() {
+        for key in [
+            KeyCode::KEY_DISPLAY_OFF,
+            KeyCode::KEY_DISPLAYTOGGLE,
+            KeyCode::KEY_PAUSE,
+        ] {
+            assert_eq!(KeyGroup::of(key), KeyGroup::Other, "{key:?}");
+        }
+    }
+}
@@ -1,7 +1,10 @@
+use evdev_rs::enums::EventCode;
+use thiserror::Error;
+
 use crate::evdev_utils::KeyCode;
 
 // Same as in evremap
-fn is_modifier(key: &KeyCode) -> bool {
+pub fn is_modifier(key: &KeyCode) -> bool {
     matches!(
         key,
         KeyCode::KEY_FN
@@ -16,160 +19,107 @@ fn is_modifier(key: &KeyCode) -> bool {
     )
 }
 
-macro_rules! modkey_translate {
-    ($keycode_val:ident, $keycode_t:ty, [$($keycode_name:ident),+]) => {
-        match $keycode_val {
-            $(
-                <$keycode_t>::$keycode_name => Some(Self::$keycode_name),
-            )+
-            _ => None
-        }
-    }
+/// Reasons evremap would reject or misbehave on a key sequence. Whether a sequence may be empty
+/// depends on where it's used, see [`ConfigFile::save_problems`].
+///
+/// [`ConfigFile::save_problems`]: crate::config_file::ConfigFile::save_problems
+#[derive(Debug, Clone, Copy, Error)]
+pub enum SequenceError {
+    #[error("{} is not a real key and can't be used", EventCode::EV_KEY(*.0))]
+    PlaceholderKey(KeyCode),
 }
 
-const MODIFIER_KEYS_AMOUNT: usize = 9;
-
-const MODIFIERS: [KeyCode; MODIFIER_KEYS_AMOUNT] = [
-    KeyCode::KEY_FN,
-    KeyCode::KEY_LEFTALT,
-    KeyCode::KEY_RIGHTALT,
-    KeyCode::KEY_LEFTMETA,
-    KeyCode::KEY_RIGHTMETA,
-    KeyCode::KEY_LEFTCTRL,
-    KeyCode::KEY_RIGHTCTRL,
-    KeyCode::KEY_LEFTSHIFT,
-    KeyCode::KEY_RIGHTSHIFT,
-];
-
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Default)]
-struct ModifierKeysMask(u16);
-
-impl ModifierKeysMask {
-    const KEY_FN: Self = Self(1);
-    const KEY_LEFTALT: Self = Self(1 << 1);
-    const KEY_RIGHTALT: Self = Self(1 << 2);
-    const KEY_LEFTMETA: Self = Self(1 << 3);
-    const KEY_RIGHTMETA: Self = Self(1 << 4);
-    const KEY_LEFTCTRL: Self = Self(1 << 5);
-    const KEY_RIGHTCTRL: Self = Self(1 << 6);
-    const KEY_LEFTSHIFT: Self = Self(1 << 7);
-    const KEY_RIGHTSHIFT: Self = Self(1 << 8);
-
-    fn from_keycode(key: KeyCode) -> Option<Self> {
-        modkey_translate!(
-            key,
-            KeyCode,
-            [
-                KEY_FN,
-                KEY_LEFTALT,
-                KEY_RIGHTALT,
-                KEY_LEFTMETA,
-                KEY_RIGHTMETA,
-                KEY_LEFTCTRL,
-                KEY_RIGHTCTRL,
-                KEY_LEFTSHIFT,
-                KEY_RIGHTSHIFT
-            ]
-        )
-    }
-
-    fn add(&mut self, key: KeyCode) {
-        if let Some(keymask) = Self::from_keycode(key) {
-            self.0 |= keymask.0
-        }
-    }
-
-    fn remove(&mut self, key: KeyCode) {
-        if let Some(keymask) = Self::from_keycode(key) {
-            self.0 &= !keymask.0
-        }
-    }
-
-    fn contains(&self, key: KeyCode) -> bool {
-        if let Some(keymask) = Self::from_keycode(key) {
-            (self.0 & keymask.0) != 0
-        } else {
-            false
-        }
-    }
-
-    fn into_iter(self) -> impl Iterator<Item = KeyCode> {
-        MODIFIERS.into_iter().filter(move |k| self.contains(*k))
-    }
-
-    fn pop(&mut self) -> Option<KeyCode> {
-        if self.0 == 0 {
-            None
-        } else {
-            for key in MODIFIERS.into_iter().rev() {
-                if self.contains(key) {
-                    self.remove(key);
-                    return Some(key);
-                }
-            }
-            // Should be unreachable
-            None
-        }
+/// Check that the keys are something evremap can match on or emit
+pub fn validate_keys(keys: &[KeyCode]) -> Result<(), SequenceError> {
+    // The kernel never reports these, and evremap can't meaningfully emit them either
+    match keys
+        .iter()
+        .find(|k| matches!(k, KeyCode::KEY_RESERVED | KeyCode::KEY_UNKNOWN))
+    {
+        Some(key) => Err(SequenceError::PlaceholderKey(*key)),
+        None => Ok(()),
     }
 }
 
+/// Set of keys pressed together, kept in the order they were added. The order matters for
+/// evremap output sequences, so it's never changed behind the user's back.
 #[derive(Debug, Clone, Default)]
 pub struct KeyCombination {
-    modifiers: ModifierKeysMask,
     keys: Vec<KeyCode>,
 }
 
 impl KeyCombination {
     pub fn clear(&mut self) {
-        self.modifiers = ModifierKeysMask::default();
         self.keys.clear();
     }
 
     pub fn push(&mut self, key: KeyCode) {
-        if is_modifier(&key) {
-            self.modifiers.add(key)
-        } else if !self.keys.contains(&key) {
+        if !self.contains(key) {
             self.keys.push(key);
         }
     }
 
     pub fn pop(&mut self) -> Option<KeyCode> {
-        if !self.keys.is_empty() {
-            self.keys.pop()
-        } else {
-            self.modifiers.pop()
-        }
+        self.keys.pop()
     }
 
     pub fn iter(&self) -> impl Iterator<Item = KeyCode> {
-        self.modifiers.into_iter().chain(self.keys.iter().copied())
+        self.keys.iter().copied()
     }
 
     pub fn to_keys(&self) -> Vec<KeyCode> {
-        self.modifiers
-            .into_iter()
-            .chain(self.keys.iter().copied())
-            .collect()
+        self.keys.clone()
+    }
+
+    /// Move the key to a new position among all keys
+    pub fn move_key(&mut self, key: KeyCode, to: usize) {
+        let Some(from) = self.keys.iter().position(|k| *k == key) else {
+            return;
+        };
+        self.keys.remove(from);
+        self.keys.insert(to.min(self.keys.len()), key);
+    }
+
+    /// Reorder the keys so that the modifiers come first, like evremap does when it builds its
+    /// own sequences. The relative order of the modifiers and of the other keys is kept.
+    pub fn sort_modifiers_first(&mut self) {
+        self.keys.sort_by_key(|k| !is_modifier(k));
     }
 
     pub fn remove_by_idx(&mut self, idx: usize) -> Option<KeyCode> {
-        let key = self.iter().nth(idx)?;
-        self.remove(key);
-        Some(key)
+        (idx < self.keys.len()).then(|| self.keys.remove(idx))
+    }
+
+    pub fn contains(&self, key: KeyCode) -> bool {
+        self.keys.contains(&key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Whether some key was added before a modifier, meaning the sequence would change if the
+    /// modifiers were moved to the front
+    pub fn has_key_before_modifier(&self) -> bool {
+        self.keys
+            .iter()
+            .skip_while(|k| is_modifier(k))
+            .any(is_modifier)
+    }
+
+    /// See [`validate_keys`]
+    pub fn validate(&self) -> Result<(), SequenceError> {
+        validate_keys(&self.keys)
     }
 
     pub fn remove(&mut self, key: KeyCode) {
-        if self.modifiers.contains(key) {
-            self.modifiers.remove(key);
-        } else if let Some((idx, _)) = self.keys.iter().enumerate().find(|(_, k)| **k == key) {
-            self.keys.remove(idx);
-        }
+        self.keys.retain(|k| *k != key);
     }
 }
 
 impl From<KeyCombination> for Vec<KeyCode> {
     fn from(value: KeyCombination) -> Self {
-        value.modifiers.into_iter().chain(value.keys).collect()
+        value.to_keys()
     }
 }
 
@@ -177,11 +127,7 @@ impl FromIterator<KeyCode> for KeyCombination {
     fn from_iter<T: IntoIterator<Item = KeyCode>>(iter: T) -> Self {
         let mut res = Self::default();
         for key in iter {
-            if is_modifier(&key) {
-                res.modifiers.add(key);
-            } else {
-                res.keys.push(key);
-            }
+            res.push(key);
         }
         res
     }
@@ -192,3 +138,37 @@ impl From<Vec<KeyCode>> for KeyCombination {
         Self::from_iter(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIXED: [KeyCode; 4] = [
+        KeyCode::KEY_A,
+        KeyCode::KEY_LEFTCTRL,
+        KeyCode::KEY_B,
+        KeyCode::KEY_LEFTSHIFT,
+    ];
+
+    #[test]
+    fn keeps_insertion_order() {
+        let combo = KeyCombination::from(MIXED.to_vec());
+        assert_eq!(combo.to_keys(), MIXED);
+        assert!(combo.has_key_before_modifier());
+    }
+
+    #[test]
+    fn sorting_modifiers_first_keeps_relative_order() {
+        let mut combo = KeyCombination::from(MIXED.to_vec());
+        combo.sort_modifiers_first();
+        assert_eq!(
+            combo.to_keys(),
+            [
+                KeyCode::KEY_LEFTCTRL,
+                KeyCode::KEY_LEFTSHIFT,
+                KeyCode::KEY_A,
+                KeyCode::KEY_B,
+            ]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    os::unix::process::CommandExt,
+    path::PathBuf,
+    process::{Child, ChildStderr, Command, ExitStatus, Stdio},
+};
+
+use thiserror::Error;
+
+use crate::config_file::{ConfigFile, ConfigFileError};
+
+#[derive(Debug, Error)]
+pub enum LiveTestError {
+    #[error("evremap was not found, make sure it is installed and in PATH")]
+    NotFound,
+    #[error("Failed to write the config for evremap: {0}")]
+    Config(#[from] ConfigFileError),
+    #[error("Failed to start evremap: {0}")]
+    Spawn(std::io::Error),
+}
+
+/// Version reported by `evremap --version`, [`None`] if it isn't installed or the output
+/// couldn't be read
+pub fn evremap_version() -> Option<String> {
+    let output = Command::new("evremap")
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let version = String::from_utf8(output.stdout).ok()?;
+    let version = version.trim();
+    (output.status.success() && !version.is_empty()).then(|| version.to_string())
+}
+
+/// evremap running with a temporary copy of the config being edited
+#[derive(Debug)]
+pub struct LiveTest {
+    child: Child,
+    config_path: PathBuf,
+    /// The process was killed by [`LiveTest::stop`] rather than exiting on its own
+    stopped: bool,
+}
+
+impl LiveTest {
+    /// Write the config to a temporary file and start `evremap remap` with it. The returned
+    /// stderr has to be read until the end to notice the process exiting.
+    pub fn start(config: &ConfigFile) -> Result<(Self, ChildStderr), LiveTestError> {
+        let config_path =
+            glib::user_runtime_dir().join(format!("evremap-gtk-test-{}.toml", std::process::id()));
+        config.save_to(&config_path)?;
+
+        let mut command = Command::new("evremap");
+        command
+            .arg("remap")
+            .arg(&config_path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        // Don't leave the keyboard remapped if the editor crashes or gets killed
+        // SAFETY: prctl is async-signal-safe and nothing is allocated in the closure
+        unsafe {
+            command.pre_exec(|| {
+                libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM);
+                Ok(())
+            });
+        }
+        let mut child = command.spawn().map_err(|e| {
+            let _ = std::fs::remove_file(&config_path);
+            match e.kind() {
+                std::io::ErrorKind::NotFound => LiveTestError::NotFound,
+                _ => LiveTestError::Spawn(e),
+            }
+        })?;
+        let stderr = child.stderr.take().expect("stderr is piped");
+
+        Ok((
+            Self {
+                child,
+                config_path,
+                stopped: false,
+            },
+            stderr,
+        ))
+    }
+
+    /// Kill the process, its stderr is closed once it's gone
+    pub fn stop(&mut self) {
+        self.stopped = true;
+        // Fails only if the process has already exited
+        let _ = self.child.kill();
+    }
+
+    pub fn was_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Wait for the process to exit and collect its exit status
+    pub fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        self.child.wait()
+    }
+}
+
+/// Kills evremap when the test is replaced or the app exits
+impl Drop for LiveTest {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+        let _ = std::fs::remove_file(&self.config_path);
+    }
+}
@@ -2,34 +2,63 @@ use std::{
     collections::{HashMap, HashSet},
     env::VarError,
     error::Error,
-    path::PathBuf,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::ExitCode,
     str::FromStr,
+    sync::mpsc,
+    time::Duration,
 };
 
 use components::{
-    device_browser::{DeviceDisplay, DeviceDisplayMsg, DeviceDisplayOutput},
-    dual_role::{DualRoleMapItem, DualRoleMapItemOutput},
+    changes_dialog::{ChangesDialog, ChangesMsg},
+    config_preview::{ConfigPreviewDialog, ConfigPreviewMsg},
+    device_browser::{DeviceDisplayOutput, DeviceFilter, DeviceGroup, DeviceGroupMsg},
+    device_picker::{DevicePickerDialog, DevicePickerMsg, DevicePickerOutput},
+    dual_role::{DualRoleMapItem, DualRoleMapItemMsg, DualRoleMapItemOutput},
     event_logger::{EventLogger, EventLoggerMsg, EventLoggerOutput},
-    remap::{RemapItem, RemapItemOutput},
+    key_seq::{KeyPresetChange, KeySeqOptions},
+    preferences::{PreferencesDialog, PreferencesMsg, PreferencesOutput},
+    remap::{RemapItem, RemapItemMsg, RemapItemOutput},
 };
-use config_file::{ConfigFile, DualRoleConfig, RemapConfig};
-use deviceinfo::DeviceInfo;
+use config_file::{ConfigFile, ConfigFileError, ConfigSource, DualRoleConfig, RemapConfig};
+use deviceinfo::{
+    DeviceField, DeviceId, DeviceInfo, DeviceInfoError, DeviceKindFilter, DeviceScan, DeviceSort,
+};
+use docs::DocsTopic;
+use evdev_utils::{KeyCode, friendly_key_name};
 use gtk::{self, prelude::*};
+use live_test::LiveTest;
 use log::LevelFilter;
-use relm4::{abstractions::Toaster, prelude::*};
+use relm4::{
+    abstractions::Toaster,
+    actions::{RelmAction, RelmActionGroup},
+    adw::prelude::*,
+    prelude::*,
+};
 use relm4_components::{
     open_dialog::{OpenDialog, OpenDialogMsg, OpenDialogResponse, OpenDialogSettings},
     save_dialog::{SaveDialog, SaveDialogMsg, SaveDialogResponse, SaveDialogSettings},
 };
+use settings::{Settings, SettingsError};
 
+mod cli;
 mod components;
 mod evdev_utils;
 mod key_combo;
 
+mod config_diff;
 mod config_file;
 mod deviceinfo;
+mod docs;
+mod live_test;
+mod settings;
 
 const APP_ID: &str = "ru.jtcf.evremap_gtk";
+/// How long the device scan summary stays visible
+const SCAN_STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+/// Oldest edits are forgotten past this many undo steps
+const MAX_UNDO_STEPS: usize = 100;
 
 /// Initialize logging for the `log` crate via glib's logging
 fn init_logging() {
@@ -63,11 +92,43 @@ fn init_logging() {
     log::debug!("Logging set up finished!")
 }
 
-fn main() {
+fn main() -> ExitCode {
     // Must be the first thing called in the app, see the safety comment in init_logging body
     init_logging();
-    let app = RelmApp::new(APP_ID);
-    app.run::<AppModel>(());
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("devices") => return cli::list_devices(&args[1..]),
+        Some("diff") => return cli::diff_configs(&args[1..]),
+        _ => {}
+    }
+
+    let config_path = args.iter().find(|a| !a.starts_with('-')).cloned();
+    let app_args = AppArgs {
+        editor_only: args.iter().any(|a| a == "--editor-only"),
+        config_path: config_path.clone().map(PathBuf::from),
+    };
+    // GTK doesn't know about our own flags and refuses to start if it gets them, it also refuses
+    // to open files on its own
+    let gtk_args = std::env::args()
+        .take(1)
+        .chain(
+            args.into_iter()
+                .filter(|a| a != "--editor-only" && Some(a) != config_path.as_ref()),
+        )
+        .collect();
+    let app = RelmApp::new(APP_ID).with_args(gtk_args);
+    app.run::<AppModel>(app_args);
+    ExitCode::SUCCESS
+}
+
+/// Options for the GUI passed on the command line
+#[derive(Debug, Default)]
+struct AppArgs {
+    /// Only show the config editor, without the device browser and the event logger
+    editor_only: bool,
+    /// Config file to open on startup
+    config_path: Option<PathBuf>,
 }
 
 /// Contains the entry buffers for the device name and phys text entries, stored in the [`AppModel`]
@@ -93,6 +154,15 @@ impl ConfigFileGtkBuf {
         }
     }
 
+    /// Device name without the surrounding whitespace, which would make it match no device
+    fn name_text(&self) -> String {
+        self.name.text().trim().to_owned()
+    }
+
+    fn phys_text(&self) -> String {
+        self.phys.text().trim().to_owned()
+    }
+
     /// Extract text from entry buffers and add the remap configs to form a config file for later
     /// saving it
     fn to_config_file(
@@ -101,26 +171,68 @@ impl ConfigFileGtkBuf {
         dual_role: Vec<DualRoleConfig>,
     ) -> ConfigFile {
         ConfigFile {
-            device_name: Some(self.name.text())
-                .filter(|s| !s.is_empty())
-                .map(|s| s.to_string()),
-            phys: Some(self.phys.text())
-                .filter(|s| !s.is_empty())
-                .map(|s| s.to_string()),
+            device_name: Some(self.name_text()).filter(|s| !s.is_empty()),
+            phys: Some(self.phys_text()).filter(|s| !s.is_empty()),
             dual_role,
             remap,
         }
     }
 }
 
+relm4::new_action_group!(WindowActionGroup, "win");
+relm4::new_stateless_action!(ConfigReferenceAction, WindowActionGroup, "config-reference");
+relm4::new_stateless_action!(PreviewConfigAction, WindowActionGroup, "preview-config");
+relm4::new_stateless_action!(ShowChangesAction, WindowActionGroup, "show-changes");
+relm4::new_stateless_action!(RevertAction, WindowActionGroup, "revert");
+relm4::new_stateless_action!(AboutAction, WindowActionGroup, "about");
+
+/// Where to save the config once it's checked for problems
+#[derive(Debug, Clone, Copy)]
+enum SaveTarget {
+    /// The current file, or ask for a path if there's none yet
+    CurrentFile,
+    /// Ask for a path, for "Save As"
+    AskPath,
+}
+
+/// Actions that would lose unsaved changes
+#[derive(Debug, Clone)]
+enum GuardedAction {
+    /// Start over with an empty config
+    New,
+    Open,
+    /// Open a file from the recent files menu
+    OpenRecent(PathBuf),
+    Close,
+}
+
 #[derive(Debug)]
 enum CommandMsg {
     /// Update the list of devices in the browser
     UpdateDeviceList {
-        devices: Vec<DeviceInfo>,
+        scan: DeviceScan,
         is_initial: bool,
     },
+    /// Connected keyboards found for [`AppMsg::UseMyKeyboardRequest`]
+    KeyboardCandidates(Result<Vec<DeviceInfo>, DeviceInfoError>),
+    /// Hide the scan status, unless a newer scan replaced it in the meantime
+    ClearScanStatus(u32),
     DeviceListRefreshError(Box<dyn Error + Send + 'static>),
+    /// Devices were plugged in or removed
+    DevicesHotplugged,
+    /// A line evremap printed while testing the config
+    TestOutput(String),
+    /// evremap test run closed its output, it has exited or is about to
+    TestFinished(u32),
+    /// Result of [`AppMsg::PrivilegedSave`]
+    PrivilegedSaveFinished {
+        path: PathBuf,
+        /// The config that was written, the editor may have changed in the meantime
+        config: ConfigFile,
+        result: Result<(), ConfigFileError>,
+    },
+    /// Installed evremap version checked for [`AppMsg::ShowAbout`]
+    ShowAbout(Option<String>),
 }
 
 #[derive(Debug)]
@@ -129,44 +241,228 @@ struct AppModel {
     remaps: FactoryVecDeque<RemapItem>,
     dual_role_remaps: FactoryVecDeque<DualRoleMapItem>,
     open_dialog: Controller<OpenDialog>,
+    import_dialog: Controller<OpenDialog>,
     save_dialog: Controller<SaveDialog>,
-    device_browser: FactoryVecDeque<DeviceDisplay>,
+    /// File the config was last opened from or saved to
+    current_path: Option<PathBuf>,
+    /// Comments and formatting of the opened file, kept when saving
+    config_source: Option<ConfigSource>,
+    /// The config was edited since it was last opened or saved
+    dirty: bool,
+    /// Action to continue with once the config is saved
+    after_save: Option<GuardedAction>,
+    /// Configs as they were before each edit, the most recent one last
+    undo_stack: Vec<ConfigFile>,
+    /// Configs undone since the last edit, the most recently undone one last
+    redo_stack: Vec<ConfigFile>,
+    /// Config as of the last recorded edit, compared against to detect the next one
+    history_snapshot: ConfigFile,
+    /// Typing goes on in a text field, like the device name or a remap comment, so the next
+    /// change of the text adds to the last undo step instead of making a new one
+    typing: bool,
+    /// Config as it was last opened or saved, [`None`] if the editor started out different from
+    /// the file. Undoing back to it leaves no unsaved changes.
+    saved_config: Option<ConfigFile>,
+    device_browser: FactoryVecDeque<DeviceGroup>,
+    /// Devices from the last scan, kept to re-sort the browser without rescanning
+    devices: Vec<DeviceInfo>,
     duplicate_names: HashSet<String>,
+    /// Keys seen from each device in the event logger this session
+    observed_keys: HashMap<DeviceId, HashSet<KeyCode>>,
     event_logger: Controller<EventLogger>,
     toaster: Toaster,
+    settings: Settings,
+    preferences: Controller<PreferencesDialog>,
+    config_preview: Controller<ConfigPreviewDialog>,
+    changes_dialog: Controller<ChangesDialog>,
+    device_picker: Controller<DevicePickerDialog>,
+    /// Contents of the recent files menu, rebuilt when the list changes
+    recent_files_box: gtk::Box,
+    device_filter: DeviceFilter,
+    device_sort: DeviceSort,
+    /// Result of the last device scan, shown briefly in the device browser
+    scan_status: Option<String>,
+    /// Tooltip with the reasons devices were skipped during the last scan
+    scan_status_details: Option<String>,
+    /// Incremented on every scan, so that a stale timeout doesn't hide a newer status
+    scan_status_generation: u32,
+    /// Device browser and event logger are removed, devices are never scanned
+    editor_only: bool,
+    /// Dropping it stops watching for devices being plugged in, see
+    /// [`AppModel::update_hotplug_watch`]
+    hotplug_watch: Option<mpsc::Sender<()>>,
+    /// A device scan is in progress
+    refreshing: bool,
+    /// evremap running with the config being edited
+    live_test: Option<LiveTest>,
+    /// Incremented on every test run, so that a finished old run doesn't end a newer one
+    test_generation: u32,
+    /// stderr of the last evremap test run
+    test_log: gtk::TextBuffer,
+    /// Show the test output once the config was tested at least once
+    test_output_shown: bool,
 }
 
 #[derive(Debug)]
 enum AppMsg {
     /// Message to trigger a redraw, completely ignored otherwise
     Ignore,
+    /// Save the config to the current file, or ask where to save it if there's none yet
+    Save,
     /// Request to save the config, triggered by the "Save As" button
     SaveRequest,
+    /// Save dialog was closed without choosing a file
+    SaveCancelled,
+    /// The config was checked for problems, or the user chose to save it anyway
+    SaveChecked(SaveTarget),
     /// User has selected a file to save the config to
     SaveResponse(PathBuf),
-    /// Request to open a config file from disk
+    /// Writing the file was not permitted, ask whether to save it as administrator
+    PrivilegedSaveRequest(PathBuf),
+    /// Save the config through pkexec
+    PrivilegedSave(PathBuf),
+    /// Request to start a new empty config, asks what to do with unsaved changes first
+    NewConfig,
+    /// Request to open a config file from disk, asks what to do with unsaved changes first
     OpenRequest,
+    /// Request to open a recently used file, asks what to do with unsaved changes first
+    OpenRecentRequest(PathBuf),
+    /// User wants to close the window, asks what to do with unsaved changes first
+    CloseRequest,
+    /// Continue with the action, discarding unsaved changes if there are any
+    Proceed(GuardedAction),
+    /// Save the config, then continue with the action
+    SaveAndProceed(GuardedAction),
+    /// Mark the config as edited or not since it was last opened or saved
+    SetDirty(bool),
+    /// Go back to the config as it was before the last edit
+    Undo,
+    /// Reapply the last undone edit
+    Redo,
     /// User has selected a config file to parse
     OpenResponse(PathBuf),
+    /// The selected config names keys unknown on this system, open it without them
+    OpenDroppingUnknownKeys(PathBuf),
+    /// Request to pick a config file to import entries from
+    ImportRequest,
+    /// User has selected a config file to import entries from
+    ImportResponse(PathBuf),
+    /// Import from a config naming keys unknown on this system, without them
+    ImportDroppingUnknownKeys(PathBuf),
+    /// Add the entries picked in the import dialog to the editor
+    ImportEntries {
+        remaps: Vec<RemapConfig>,
+        dual_role: Vec<DualRoleConfig>,
+    },
     AddRemap,
+    /// Add a remap with the keys captured in the event logger as its input
+    AddCapturedRemap(Vec<KeyCode>),
+    /// Add a remap for each of the keys picked in the event logger, with the key as its input
+    AddKeyRemaps(Vec<KeyCode>),
+    /// User wants to delete a remap, asks for confirmation if enabled
+    DeleteRemapRequest(DynamicIndex),
     DeleteRemap(DynamicIndex),
+    /// User wants to delete all remaps, asks for confirmation
+    ClearRemapsRequest,
+    ClearRemaps,
+    /// Input or output keys of a remap were edited
+    RemapChanged,
+    /// Comment of a remap was edited
+    RemapCommentChanged,
+    /// Swap the remap with the one above or below it
+    MoveRemap(DynamicIndex, gtk::DirectionType),
+    /// Insert a copy of the remap right after it
+    DuplicateRemap(DynamicIndex),
+    /// Move the focus to the remap entry above or below the focused one
+    FocusRemap(gtk::DirectionType),
+    /// Show or hide the key sequences of all remaps, leaving only their summaries
+    SetRemapsExpanded(bool),
     AddDualRoleRemap,
+    /// User wants to delete a dual-role entry, asks for confirmation if enabled
+    DeleteDualRoleRemapRequest(DynamicIndex),
     DeleteDualRoleRemap(DynamicIndex),
+    /// User wants to delete all dual-role entries, asks for confirmation
+    ClearDualRoleRequest,
+    ClearDualRole,
+    /// Swap the dual-role entry with the one above or below it
+    MoveDualRoleRemap(DynamicIndex, gtk::DirectionType),
+    /// Insert a copy of the dual-role entry right after it
+    DuplicateDualRoleRemap(DynamicIndex),
+    /// Trigger key or one of the sequences of a dual-role entry was edited
+    DualRoleRemapChanged,
     /// Copy the device's name and phys to the editor
     SetDevice(DeviceInfo),
+    /// Look for a connected keyboard to use as the config device
+    UseMyKeyboardRequest,
+    /// Pick one of the connected devices whose name matches a pattern as the config device
+    FindDevicesByPattern,
+    /// Use the device as the config device, asks for confirmation if the fields are filled in
+    UseDevice(DeviceInfo),
     /// Request to update teh list of devices
     RefreshDevices {
         is_initial: bool,
     },
     /// Set the device for event logging
     SetLoggerDevice(DeviceInfo),
+    /// Log the events of the only connected device matching the editor's name and phys
+    ViewConfigDeviceEvents,
+    /// Stop reading events from every device and the running config test
+    EmergencyStop,
+    /// Forget the last logger device so it's not restored on the next start
+    LoggerDeviceCleared,
+    /// A key was pressed for the first time on the device in the event logger
+    KeyObserved(DeviceId, KeyCode),
+    /// The event log was saved to a file
+    LogExported(PathBuf),
+    /// A key was pressed or released on the device in the event logger
+    LoggedKeyEvent {
+        device: DeviceId,
+        key: KeyCode,
+        pressed: bool,
+        time: Duration,
+    },
+    /// Device name or phys in the editor was changed
+    ConfigDeviceChanged,
+    /// Store the alias of a device in the settings
+    SetDeviceAlias {
+        id: DeviceId,
+        alias: Option<String>,
+    },
+    /// Run evremap with the current config until stopped
+    StartTest,
+    /// Stop the evremap test run
+    StopTest,
     /// Display an error in the UI
     ReportError {
         error: Box<dyn Error + Send + 'static>,
         extra_context: Option<String>,
     },
-    ShowHiddenDevices,
-    HideUselessDevices,
+    SetDeviceKindFilter(DeviceKindFilter),
+    SetShowVirtual(bool),
+    /// Show only the devices matching the search text
+    FilterDevices(String),
+    /// Change the device list order without rescanning the devices
+    SetDeviceSort(DeviceSort),
+    OpenPreferences,
+    /// Open a page of the evremap documentation in the browser
+    OpenDocs(DocsTopic),
+    /// Persist the settings changed in the preferences dialog
+    SettingsChanged(Settings),
+    /// Remember the visible page of the main stack for the next launch
+    PageChanged(String),
+    /// Show the TOML that saving would write, without saving it
+    PreviewConfig,
+    /// Compare what saving would write with the file on disk
+    ShowChanges,
+    /// Read the current file again, dropping the unsaved changes
+    RevertRequest,
+    /// Show the version and credits, once the installed evremap version is checked
+    ShowAbout,
+    DeviceFieldCopied(DeviceField),
+    /// Save or remove a key sequence preset
+    KeyPreset(KeyPresetChange),
+    /// The log line limit was changed on the event logger page
+    LogMaxLinesChanged(u32),
 }
 
 impl AppMsg {
@@ -181,31 +477,101 @@ impl AppMsg {
 
 #[relm4::component]
 impl Component for AppModel {
-    type Init = ();
+    type Init = AppArgs;
     type Input = AppMsg;
     type Output = ();
     type CommandOutput = CommandMsg;
 
     view! {
         gtk::Window {
-            set_title: Some("evremap config editor"),
-            set_default_size: (600, 400),
+            #[watch]
+            set_title: Some(&model.window_title()),
+            connect_close_request[sender] => move |_| {
+                // Closing is handled by AppMsg::Proceed once unsaved changes are dealt with
+                sender.input(AppMsg::CloseRequest);
+                glib::Propagation::Stop
+            },
 
             #[wrap(Some)]
             set_titlebar = &gtk::HeaderBar {
+                pack_start = &gtk::Button {
+                    set_label: "New",
+                    set_tooltip_text: Some("Start a new empty config"),
+                    connect_clicked => AppMsg::NewConfig,
+                },
                 pack_start = &gtk::Button {
                     set_label: "Open",
+                    set_tooltip_text: Some("Open a config (Ctrl+O)"),
                     connect_clicked => AppMsg::OpenRequest,
                 },
+                pack_start = &gtk::MenuButton {
+                    set_icon_name: "document-open-recent-symbolic",
+                    set_tooltip_text: Some("Recent files"),
+                    #[watch]
+                    set_sensitive: !model.settings.recent_files.is_empty(),
+                    #[wrap(Some)]
+                    set_popover = &gtk::Popover {
+                        #[local_ref]
+                        recent_files_box -> gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+                        },
+                    },
+                },
+                pack_start = &gtk::Button::from_icon_name("edit-undo-symbolic") {
+                    set_tooltip_text: Some("Undo (Ctrl+Z)"),
+                    #[watch]
+                    set_sensitive: !model.undo_stack.is_empty(),
+                    connect_clicked => AppMsg::Undo,
+                },
+                pack_start = &gtk::Button::from_icon_name("edit-redo-symbolic") {
+                    set_tooltip_text: Some("Redo (Ctrl+Shift+Z)"),
+                    #[watch]
+                    set_sensitive: !model.redo_stack.is_empty(),
+                    connect_clicked => AppMsg::Redo,
+                },
+                pack_start = &gtk::Button {
+                    set_label: "Import…",
+                    set_tooltip_text: Some("Import selected entries from another config"),
+                    connect_clicked => AppMsg::ImportRequest,
+                },
+                pack_end = &gtk::MenuButton {
+                    set_icon_name: "open-menu-symbolic",
+                    set_tooltip_text: Some("Main menu"),
+                    set_menu_model: Some(&main_menu),
+                },
+                pack_end = &gtk::Button::from_icon_name("process-stop-symbolic") {
+                    set_visible: !model.editor_only,
+                    set_tooltip_text: Some("Stop event logging and the config test (Ctrl+Shift+Esc)"),
+                    connect_clicked => AppMsg::EmergencyStop,
+                },
+                pack_end = &gtk::Button::from_icon_name("preferences-system-symbolic") {
+                    set_tooltip_text: Some("Preferences"),
+                    connect_clicked => AppMsg::OpenPreferences,
+                },
                 pack_end = &gtk::Button {
                     set_label: "Save As",
+                    set_tooltip_text: Some("Save to a new file (Ctrl+Shift+S)"),
                     connect_clicked => AppMsg::SaveRequest,
 
                     #[watch]
-                    set_sensitive: !device_name_entry.text().is_empty()
+                    set_sensitive: !model.config.name_text().is_empty()
+                },
+                pack_end = &gtk::Button {
+                    set_label: "Save",
+                    connect_clicked => AppMsg::Save,
+
+                    #[watch]
+                    set_sensitive: model.current_path.is_some() && !model.config.name_text().is_empty(),
+                    #[watch]
+                    set_tooltip_text: model
+                        .current_path
+                        .as_ref()
+                        .map(|p| format!("Save to {} (Ctrl+S)", p.display()))
+                        .as_deref(),
                 },
                 #[wrap(Some)]
                 set_title_widget = &gtk::StackSwitcher {
+                    set_visible: !model.editor_only,
                     set_stack: Some(&contents_stack)
                 },
             },
@@ -216,6 +582,13 @@ impl Component for AppModel {
 
                 #[name(contents_stack)]
                 gtk::Stack {
+                    connect_visible_child_name_notify[sender] => move |stack| {
+                        if let Some(name) = stack.visible_child_name() {
+                            sender.input(AppMsg::PageChanged(name.into()));
+                        }
+                    },
+
+                    #[name(editor_page)]
                     add_child = &gtk::Box {
                         set_orientation: gtk::Orientation::Vertical,
                         set_spacing: 12,
@@ -230,11 +603,22 @@ impl Component for AppModel {
                             set_hexpand: true,
                                 set_placeholder_text: Some("Device name (required)"),
                                 set_buffer: &model.config.name,
-                                connect_changed => AppMsg::Ignore,
+                                connect_changed => AppMsg::ConfigDeviceChanged,
                                 #[watch]
                                 set_class_active: ("warning", model.should_display_name_warning()),
                             },
 
+                            gtk::Button {
+                                set_label: "Use my keyboard",
+                                set_tooltip_text: Some("Fill in the name and phys of a connected keyboard"),
+                                connect_clicked => AppMsg::UseMyKeyboardRequest,
+                            },
+
+                            gtk::Button::from_icon_name("system-search-symbolic") {
+                                set_tooltip_text: Some("Find connected devices by a name pattern"),
+                                connect_clicked => AppMsg::FindDevicesByPattern,
+                            },
+
                             gtk::Image::from_icon_name("dialog-warning-symbolic") {
                                 #[watch]
                                 set_visible: model.should_display_name_warning(),
@@ -246,6 +630,55 @@ impl Component for AppModel {
                         gtk::Entry {
                             set_placeholder_text: Some("Device phys (optional)"),
                             set_buffer: &model.config.phys,
+                            connect_changed => AppMsg::ConfigDeviceChanged,
+                        },
+
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Horizontal,
+                            set_spacing: 6,
+                            #[watch]
+                            set_visible: model.device_match_status().is_some(),
+
+                            gtk::Label {
+                                set_halign: gtk::Align::Start,
+                                set_hexpand: true,
+                                set_ellipsize: gtk::pango::EllipsizeMode::Middle,
+                                #[watch]
+                                set_label: &model
+                                    .device_match_status()
+                                    .map(|(status, _)| status)
+                                    .unwrap_or_default(),
+                                #[watch]
+                                set_class_active: ("success", model.device_match_status().is_some_and(|(_, ok)| ok)),
+                                #[watch]
+                                set_class_active: ("warning", model.device_match_status().is_some_and(|(_, ok)| !ok)),
+                            },
+
+                            gtk::Button {
+                                set_label: "View events",
+                                set_tooltip_text: Some("Log the events of the matching device"),
+                                #[watch]
+                                set_visible: model.config_devices().len() == 1,
+                                connect_clicked[sender, contents_stack] => move |_| {
+                                    sender.input(AppMsg::ViewConfigDeviceEvents);
+                                    contents_stack.set_visible_child_name("event_logger");
+                                },
+                            },
+                        },
+
+                        adw::Banner {
+                            #[watch]
+                            set_revealed: !model.trigger_collisions().is_empty(),
+                            #[watch]
+                            set_title: &format!(
+                                "Used both as dual-role triggers and in remap inputs: {}",
+                                model
+                                    .trigger_collisions()
+                                    .into_iter()
+                                    .map(friendly_key_name)
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            ),
                         },
 
                         gtk::Box {
@@ -256,6 +689,12 @@ impl Component for AppModel {
                                 set_text: "Remap",
                             },
 
+                            gtk::Button::from_icon_name("help-about-symbolic") {
+                                set_has_frame: false,
+                                set_tooltip_text: Some("Remap documentation"),
+                                connect_clicked => AppMsg::OpenDocs(DocsTopic::Remap),
+                            },
+
                             gtk::Box {
                                 set_orientation: gtk::Orientation::Vertical,
                                 set_valign: gtk::Align::Center,
@@ -265,6 +704,23 @@ impl Component for AppModel {
                                 }
                             },
 
+                            gtk::Button::from_icon_name("pan-down-symbolic") {
+                                set_tooltip_text: Some("Expand all"),
+                                connect_clicked => AppMsg::SetRemapsExpanded(true),
+                            },
+
+                            gtk::Button::from_icon_name("pan-up-symbolic") {
+                                set_tooltip_text: Some("Collapse all"),
+                                connect_clicked => AppMsg::SetRemapsExpanded(false),
+                            },
+
+                            gtk::Button::from_icon_name("edit-clear-all-symbolic") {
+                                set_tooltip_text: Some("Delete all remaps"),
+                                #[watch]
+                                set_sensitive: !model.remaps.is_empty(),
+                                connect_clicked => AppMsg::ClearRemapsRequest,
+                            },
+
                             gtk::Button::from_icon_name("list-add-symbolic") {
                                 connect_clicked => AppMsg::AddRemap
                             }
@@ -290,6 +746,12 @@ impl Component for AppModel {
                                         set_text: "Dual-role",
                                     },
 
+                                    gtk::Button::from_icon_name("help-about-symbolic") {
+                                        set_has_frame: false,
+                                        set_tooltip_text: Some("Dual-role documentation"),
+                                        connect_clicked => AppMsg::OpenDocs(DocsTopic::DualRole),
+                                    },
+
                                     gtk::Box {
                                         set_orientation: gtk::Orientation::Vertical,
                                         set_valign: gtk::Align::Center,
@@ -299,6 +761,13 @@ impl Component for AppModel {
                                         }
                                     },
 
+                                    gtk::Button::from_icon_name("edit-clear-all-symbolic") {
+                                        set_tooltip_text: Some("Delete all dual-role entries"),
+                                        #[watch]
+                                        set_sensitive: !model.dual_role_remaps.is_empty(),
+                                        connect_clicked => AppMsg::ClearDualRoleRequest,
+                                    },
+
                                     gtk::Button::from_icon_name("list-add-symbolic") {
                                         connect_clicked => AppMsg::AddDualRoleRemap
                                     }
@@ -310,39 +779,142 @@ impl Component for AppModel {
                                     set_spacing: 6,
                                 },
                             }
+                        },
+
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Horizontal,
+                            set_spacing: 6,
+
+                            gtk::Button {
+                                set_label: "Test",
+                                set_tooltip_text: Some("Run evremap with the current config until stopped"),
+                                #[watch]
+                                set_visible: model.live_test.is_none(),
+                                #[watch]
+                                set_sensitive: !model.config.name_text().is_empty(),
+                                connect_clicked => AppMsg::StartTest,
+                            },
+
+                            gtk::Button {
+                                set_label: "Stop",
+                                add_css_class: "destructive-action",
+                                #[watch]
+                                set_visible: model.live_test.is_some(),
+                                connect_clicked => AppMsg::StopTest,
+                            },
+
+                            gtk::Expander {
+                                set_label: Some("evremap output"),
+                                set_hexpand: true,
+                                set_expanded: true,
+                                #[watch]
+                                set_visible: model.test_output_shown,
+
+                                gtk::ScrolledWindow {
+                                    set_min_content_height: 120,
+
+                                    gtk::TextView {
+                                        set_buffer: Some(&model.test_log),
+                                        set_editable: false,
+                                        set_cursor_visible: false,
+                                        set_monospace: true,
+                                    }
+                                }
+                            }
                         }
                     } -> {
                         set_name: "editor",
-                        set_title: "Editor"
+                        #[watch]
+                        set_title: &model.editor_page_title(),
                     },
 
+                    #[name(device_browser_page)]
                     add_child = &gtk::Box {
                         set_orientation: gtk::Orientation::Vertical,
 
+                        gtk::SearchEntry {
+                            set_placeholder_text: Some("Search by name, phys or alias"),
+                            set_margin_top: 6,
+                            set_margin_start: 6,
+                            set_margin_end: 6,
+                            connect_search_changed[sender] => move |entry| {
+                                sender.input(AppMsg::FilterDevices(entry.text().to_string()))
+                            }
+                        },
+
                         gtk::Box {
                             set_orientation: gtk::Orientation::Horizontal,
                             set_spacing: 6,
                             set_margin_all: 6,
 
-                            #[name(hidden_devs_toggle)]
-                            gtk::CheckButton::with_label("Hide devices without supported events") {
-                                set_active: true,
+                            gtk::DropDown::from_strings(&DeviceKindFilter::ALL.map(|k| k.label())) {
+                                set_tooltip_text: Some("Which devices to show"),
+                                #[watch]
+                                #[block_signal(device_kind_handler)]
+                                set_selected: DeviceKindFilter::ALL
+                                    .iter()
+                                    .position(|k| *k == model.device_filter.kind)
+                                    .unwrap_or(0) as u32,
+                                connect_selected_notify[sender] => move |dd| {
+                                    if let Some(kind) = DeviceKindFilter::ALL.get(dd.selected() as usize) {
+                                        sender.input(AppMsg::SetDeviceKindFilter(*kind));
+                                    }
+                                } @device_kind_handler
+                            },
+
+                            gtk::CheckButton::with_label("Show virtual devices") {
+                                set_tooltip_text: Some("Devices created by software, like the output device of a running evremap"),
+                                #[watch]
+                                #[block_signal(show_virtual_handler)]
+                                set_active: model.device_filter.show_virtual,
                                 connect_toggled[sender] => move |cb| {
-                                    sender.input(
-                                    match cb.is_active() {
-                                        true => AppMsg::HideUselessDevices,
-                                        false => AppMsg::ShowHiddenDevices,
-                                    })
-                                }
+                                    sender.input(AppMsg::SetShowVirtual(cb.is_active()))
+                                } @show_virtual_handler
+                            },
+
+                            gtk::DropDown::from_strings(&DeviceSort::ALL.map(|s| s.label())) {
+                                set_tooltip_text: Some("Sort devices by"),
+                                #[watch]
+                                #[block_signal(device_sort_handler)]
+                                set_selected: DeviceSort::ALL
+                                    .iter()
+                                    .position(|s| *s == model.device_sort)
+                                    .unwrap_or(0) as u32,
+                                connect_selected_notify[sender] => move |dd| {
+                                    if let Some(sort) = DeviceSort::ALL.get(dd.selected() as usize) {
+                                        sender.input(AppMsg::SetDeviceSort(*sort));
+                                    }
+                                } @device_sort_handler
+                            },
+
+                            gtk::Label {
+                                set_halign: gtk::Align::End,
+                                set_hexpand: true,
+                                add_css_class: "dim-label",
+                                #[watch]
+                                set_visible: model.scan_status.is_some(),
+                                #[watch]
+                                set_label: model.scan_status.as_deref().unwrap_or_default(),
+                                #[watch]
+                                set_tooltip_text: model.scan_status_details.as_deref(),
+                            },
+
+                            gtk::Spinner {
+                                set_tooltip_text: Some("Scanning devices"),
+                                #[watch]
+                                set_visible: model.refreshing,
+                                #[watch]
+                                set_spinning: model.refreshing,
                             },
 
                             gtk::Button::from_icon_name("view-refresh-symbolic") {
                                 set_tooltip_text: Some("Refresh device list"),
+                                #[watch]
+                                set_sensitive: !model.refreshing,
                                 set_has_frame: false,
                                 add_css_class: "device-list-refresh-button",
                                 connect_clicked => AppMsg::RefreshDevices { is_initial: false },
                                 set_halign: gtk::Align::End,
-                                set_hexpand: true,
                             },
                         },
 
@@ -359,7 +931,8 @@ impl Component for AppModel {
                         }
                     } -> {
                         set_name: "devbrowser",
-                        set_title: "Devices"
+                        #[watch]
+                        set_title: &model.devices_page_title(),
                     },
 
                     #[local_ref]
@@ -372,27 +945,56 @@ impl Component for AppModel {
         }
     }
 
+    menu! {
+        main_menu: {
+            "Preview config" => PreviewConfigAction,
+            "Show changes" => ShowChangesAction,
+            "Revert to saved" => RevertAction,
+            "evremap config reference" => ConfigReferenceAction,
+            section! {
+                "About evremap config editor" => AboutAction,
+            }
+        }
+    }
+
     fn init(
-        _init: Self::Init,
+        init: Self::Init,
         root: Self::Root,
         sender: relm4::ComponentSender<Self>,
     ) -> relm4::ComponentParts<Self> {
         let save_dialog = SaveDialog::builder()
             .transient_for_native(&root)
-            .launch(SaveDialogSettings::default())
+            .launch(SaveDialogSettings {
+                filters: config_file_filters(),
+                ..Default::default()
+            })
             .forward(sender.input_sender(), |response| match response {
-                SaveDialogResponse::Cancel => AppMsg::Ignore,
+                SaveDialogResponse::Cancel => AppMsg::SaveCancelled,
                 SaveDialogResponse::Accept(path) => AppMsg::SaveResponse(path),
             });
 
         let open_dialog = OpenDialog::builder()
             .transient_for_native(&root)
-            .launch(OpenDialogSettings::default())
+            .launch(OpenDialogSettings {
+                filters: config_file_filters(),
+                ..Default::default()
+            })
             .forward(sender.input_sender(), |response| match response {
                 OpenDialogResponse::Cancel => AppMsg::Ignore,
                 OpenDialogResponse::Accept(path) => AppMsg::OpenResponse(path),
             });
 
+        let import_dialog = OpenDialog::builder()
+            .transient_for_native(&root)
+            .launch(OpenDialogSettings {
+                filters: config_file_filters(),
+                ..Default::default()
+            })
+            .forward(sender.input_sender(), |response| match response {
+                OpenDialogResponse::Cancel => AppMsg::Ignore,
+                OpenDialogResponse::Accept(path) => AppMsg::ImportResponse(path),
+            });
+
         let event_logger =
             EventLogger::builder()
                 .launch(None)
@@ -401,18 +1003,58 @@ impl Component for AppModel {
                         error: e,
                         extra_context: msg,
                     },
+                    EventLoggerOutput::DeviceCleared => AppMsg::LoggerDeviceCleared,
+                    EventLoggerOutput::KeyObserved(dev, key) => AppMsg::KeyObserved(dev, key),
+                    EventLoggerOutput::CaptureSequence(keys) => AppMsg::AddCapturedRemap(keys),
+                    EventLoggerOutput::RemapEachKey(keys) => AppMsg::AddKeyRemaps(keys),
+                    EventLoggerOutput::LogExported(path) => AppMsg::LogExported(path),
+                    EventLoggerOutput::KeyEvent {
+                        device,
+                        key,
+                        pressed,
+                        time,
+                    } => AppMsg::LoggedKeyEvent {
+                        device,
+                        key,
+                        pressed,
+                        time,
+                    },
+                    EventLoggerOutput::Copied(field) => AppMsg::DeviceFieldCopied(field),
+                    EventLoggerOutput::MaxLinesChanged(max_lines) => {
+                        AppMsg::LogMaxLinesChanged(max_lines)
+                    }
                 });
 
         let remaps = FactoryVecDeque::builder()
             .launch(gtk::Box::default())
             .forward(sender.input_sender(), |out| match out {
-                RemapItemOutput::Delete(idx) => AppMsg::DeleteRemap(idx),
+                RemapItemOutput::Delete(idx) => AppMsg::DeleteRemapRequest(idx),
+                RemapItemOutput::Changed => AppMsg::RemapChanged,
+                RemapItemOutput::CommentChanged => AppMsg::RemapCommentChanged,
+                RemapItemOutput::MoveUp(idx) => AppMsg::MoveRemap(idx, gtk::DirectionType::Up),
+                RemapItemOutput::MoveDown(idx) => AppMsg::MoveRemap(idx, gtk::DirectionType::Down),
+                RemapItemOutput::Duplicate(idx) => AppMsg::DuplicateRemap(idx),
+                RemapItemOutput::PasteFailed(e) => AppMsg::err_msg(e, Some("Failed to paste keys")),
+                RemapItemOutput::Preset(change) => AppMsg::KeyPreset(change),
             });
 
         let dual_role_remaps = FactoryVecDeque::builder()
             .launch(gtk::Box::default())
             .forward(sender.input_sender(), |out| match out {
-                DualRoleMapItemOutput::Delete(idx) => AppMsg::DeleteDualRoleRemap(idx),
+                DualRoleMapItemOutput::Delete(idx) => AppMsg::DeleteDualRoleRemapRequest(idx),
+                // Also redraws the trigger key collision banner
+                DualRoleMapItemOutput::Changed => AppMsg::DualRoleRemapChanged,
+                DualRoleMapItemOutput::MoveUp(idx) => {
+                    AppMsg::MoveDualRoleRemap(idx, gtk::DirectionType::Up)
+                }
+                DualRoleMapItemOutput::MoveDown(idx) => {
+                    AppMsg::MoveDualRoleRemap(idx, gtk::DirectionType::Down)
+                }
+                DualRoleMapItemOutput::Duplicate(idx) => AppMsg::DuplicateDualRoleRemap(idx),
+                DualRoleMapItemOutput::PasteFailed(e) => {
+                    AppMsg::err_msg(e, Some("Failed to paste keys"))
+                }
+                DualRoleMapItemOutput::Preset(change) => AppMsg::KeyPreset(change),
             });
 
         let device_browser = FactoryVecDeque::builder()
@@ -420,20 +1062,80 @@ impl Component for AppModel {
             .forward(sender.input_sender(), |out| match out {
                 DeviceDisplayOutput::SetDevice(dev) => AppMsg::SetDevice(dev),
                 DeviceDisplayOutput::UseDeviceInLogger(dev) => AppMsg::SetLoggerDevice(dev),
+                DeviceDisplayOutput::SetAlias { id, alias } => AppMsg::SetDeviceAlias { id, alias },
+                DeviceDisplayOutput::Copied(field) => AppMsg::DeviceFieldCopied(field),
             });
 
-        sender.input(AppMsg::RefreshDevices { is_initial: true });
+        let settings = Settings::load().unwrap_or_else(|e| {
+            sender.input(AppMsg::err_msg(e, Some("Failed to load settings")));
+            Settings::default()
+        });
+        event_logger.emit(EventLoggerMsg::SetShowKeyCodes(!settings.hide_key_codes));
+        event_logger.emit(EventLoggerMsg::SetMaxLines(settings.event_logger.max_lines));
+
+        let preferences = PreferencesDialog::builder()
+            .launch(settings.clone())
+            .forward(sender.input_sender(), |out| match out {
+                PreferencesOutput::Changed(settings) => AppMsg::SettingsChanged(settings),
+            });
+        let config_preview = ConfigPreviewDialog::builder().launch(()).detach();
+        let changes_dialog = ChangesDialog::builder().launch(()).detach();
+        let device_picker = DevicePickerDialog::builder().launch(()).forward(
+            sender.input_sender(),
+            |out| match out {
+                DevicePickerOutput::Use(dev) => AppMsg::UseDevice(dev),
+            },
+        );
+
+        if let Some(path) = init.config_path {
+            sender.input(AppMsg::OpenResponse(path));
+        }
+
+        let editor_only = init.editor_only || settings.editor_only;
+        if !editor_only {
+            sender.input(AppMsg::RefreshDevices { is_initial: true });
+        }
 
-        let model = Self {
+        let mut model = Self {
             config: ConfigFileGtkBuf::default(),
             remaps,
             dual_role_remaps,
             save_dialog,
+            current_path: None,
+            config_source: None,
+            dirty: false,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            history_snapshot: ConfigFile::default(),
+            typing: false,
+            saved_config: Some(ConfigFile::default()),
+            after_save: None,
             open_dialog,
+            import_dialog,
             device_browser,
+            devices: Vec::new(),
             duplicate_names: HashSet::new(),
+            observed_keys: HashMap::new(),
             event_logger,
             toaster: Toaster::default(),
+            settings,
+            preferences,
+            config_preview,
+            changes_dialog,
+            device_picker,
+            recent_files_box: gtk::Box::default(),
+            device_filter: DeviceFilter::default(),
+            device_sort: DeviceSort::default(),
+            scan_status: None,
+            scan_status_details: None,
+            scan_status_generation: 0,
+            editor_only,
+            hotplug_watch: None,
+            refreshing: false,
+            live_test: None,
+            test_generation: 0,
+            test_log: gtk::TextBuffer::new(None),
+            test_output_shown: false,
         };
 
         let remaps_box = model.remaps.widget();
@@ -441,75 +1143,725 @@ impl Component for AppModel {
         let device_browser_box = model.device_browser.widget();
         let event_logger_box = model.event_logger.widget();
         let toast_overlay = model.toaster.overlay_widget();
+        let recent_files_box = &model.recent_files_box;
         let widgets = view_output!();
+        model.update_recent_files_menu(&sender);
+        model.update_hotplug_watch(&sender);
+
+        let mut window_actions = RelmActionGroup::<WindowActionGroup>::new();
+        let docs_sender = sender.clone();
+        window_actions.add_action(RelmAction::<ConfigReferenceAction>::new_stateless(
+            move |_| docs_sender.input(AppMsg::OpenDocs(DocsTopic::ConfigReference)),
+        ));
+        let preview_sender = sender.clone();
+        window_actions.add_action(RelmAction::<PreviewConfigAction>::new_stateless(
+            move |_| preview_sender.input(AppMsg::PreviewConfig),
+        ));
+        let changes_sender = sender.clone();
+        window_actions.add_action(RelmAction::<ShowChangesAction>::new_stateless(move |_| {
+            changes_sender.input(AppMsg::ShowChanges)
+        }));
+        let revert_sender = sender.clone();
+        window_actions.add_action(RelmAction::<RevertAction>::new_stateless(move |_| {
+            revert_sender.input(AppMsg::RevertRequest)
+        }));
+        let about_sender = sender.clone();
+        window_actions.add_action(RelmAction::<AboutAction>::new_stateless(move |_| {
+            about_sender.input(AppMsg::ShowAbout)
+        }));
+        window_actions.register_for_widget(&root);
+
+        let window_settings = &model.settings.window;
+        root.set_default_size(window_settings.width, window_settings.height);
+        root.set_maximized(window_settings.maximized);
+
+        if model.editor_only {
+            widgets.contents_stack.remove(&widgets.device_browser_page);
+            widgets.contents_stack.remove(event_logger_box);
+        } else if let Some(page) = &window_settings.page
+            && widgets.contents_stack.child_by_name(page).is_some()
+        {
+            widgets.contents_stack.set_visible_child_name(page);
+        }
+
+        let editor_shortcuts = gtk::ShortcutController::new();
+        for (accel, direction) in [
+            ("<Control>Down", gtk::DirectionType::Down),
+            ("<Control>Up", gtk::DirectionType::Up),
+        ] {
+            let sender = sender.clone();
+            editor_shortcuts.add_shortcut(gtk::Shortcut::new(
+                gtk::ShortcutTrigger::parse_string(accel),
+                Some(gtk::CallbackAction::new(move |_, _| {
+                    sender.input(AppMsg::FocusRemap(direction));
+                    glib::Propagation::Stop
+                })),
+            ));
+        }
+        widgets.editor_page.add_controller(editor_shortcuts);
+
+        // Works regardless of the focused widget or the active page
+        let global_shortcuts = gtk::ShortcutController::new();
+        global_shortcuts.set_scope(gtk::ShortcutScope::Global);
+        let stop_sender = sender.clone();
+        global_shortcuts.add_shortcut(gtk::Shortcut::new(
+            gtk::ShortcutTrigger::parse_string("<Control><Shift>Escape"),
+            Some(gtk::CallbackAction::new(move |_, _| {
+                stop_sender.input(AppMsg::EmergencyStop);
+                glib::Propagation::Stop
+            })),
+        ));
+        root.add_controller(global_shortcuts);
+
+        // Local scope runs in the bubble phase, so a recording KeySeqInput gets the keys first.
+        // Text entries keep their own undo.
+        let file_shortcuts = gtk::ShortcutController::new();
+        let file_accels: [(&str, fn() -> AppMsg); 5] = [
+            ("<Control>o", || AppMsg::OpenRequest),
+            ("<Control>s", || AppMsg::Save),
+            ("<Control><Shift>s", || AppMsg::SaveRequest),
+            ("<Control>z", || AppMsg::Undo),
+            ("<Control><Shift>z", || AppMsg::Redo),
+        ];
+        for (accel, msg) in file_accels {
+            let sender = sender.clone();
+            file_shortcuts.add_shortcut(gtk::Shortcut::new(
+                gtk::ShortcutTrigger::parse_string(accel),
+                Some(gtk::CallbackAction::new(move |widget, _| {
+                    let typing = widget
+                        .root()
+                        .and_then(|root| root.focus())
+                        .is_some_and(|focus| focus.is::<gtk::Text>());
+                    if typing {
+                        return glib::Propagation::Proceed;
+                    }
+                    sender.input(msg());
+                    glib::Propagation::Stop
+                })),
+            ));
+        }
+        root.add_controller(file_shortcuts);
 
         relm4::ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>, root: &Self::Root) {
         match message {
             AppMsg::Ignore => {}
-            AppMsg::SaveRequest => self.save_dialog.emit(SaveDialogMsg::Save),
-            AppMsg::SaveResponse(path) => {
-                if let Err(e) = self.to_config_file().save_to(&path) {
-                    sender.input(AppMsg::err_msg(e, Some("Failed to save config file")))
+            AppMsg::SaveRequest => self.check_before_save(root, &sender, SaveTarget::AskPath),
+            AppMsg::Save => self.check_before_save(root, &sender, SaveTarget::CurrentFile),
+            AppMsg::SaveChecked(target) => match (target, self.current_path.clone()) {
+                (SaveTarget::CurrentFile, Some(path)) => self.save_config(path, &sender),
+                _ => self.save_dialog.emit(SaveDialogMsg::Save),
+            },
+            AppMsg::SaveResponse(path) => self.save_config(path, &sender),
+            AppMsg::SaveCancelled => self.after_save = None,
+            AppMsg::PrivilegedSaveRequest(path) => Self::ask_privileged_save(root, &sender, path),
+            AppMsg::PrivilegedSave(path) => {
+                let config = self.to_config_file();
+                let source = self.config_source.clone();
+                let backup = self.settings.saving.backup;
+                // pkexec waits for the authentication dialog, don't block the UI meanwhile
+                sender.spawn_oneshot_command(move || {
+                    let result = config.save_to_privileged(&path, source.as_ref(), backup);
+                    CommandMsg::PrivilegedSaveFinished {
+                        path,
+                        config,
+                        result,
+                    }
+                });
+            }
+            AppMsg::NewConfig => self.guard_unsaved(root, &sender, GuardedAction::New),
+            AppMsg::OpenRequest => self.guard_unsaved(root, &sender, GuardedAction::Open),
+            AppMsg::OpenRecentRequest(path) => {
+                self.guard_unsaved(root, &sender, GuardedAction::OpenRecent(path))
+            }
+            AppMsg::CloseRequest => self.guard_unsaved(root, &sender, GuardedAction::Close),
+            AppMsg::Proceed(GuardedAction::New) => {
+                self.current_path = None;
+                self.config_source = None;
+                self.load(ConfigFile::default());
+                self.reset_history();
+                self.saved_config = Some(self.history_snapshot.clone());
+                self.update_observed_hints();
+                self.update_remap_conflicts();
+                // Clearing the device fields queues change notifications, this has to come after
+                // them
+                sender.input(AppMsg::SetDirty(false));
+            }
+            AppMsg::Proceed(GuardedAction::Open) => self.open_dialog.emit(OpenDialogMsg::Open),
+            AppMsg::Proceed(GuardedAction::OpenRecent(path)) => {
+                if path.exists() {
+                    sender.input(AppMsg::OpenResponse(path));
                 } else {
                     self.show_message_toast(format!(
-                        "Successfully saved config to {}",
+                        "{} no longer exists, removed it from the recent files",
                         path.display()
                     ));
+                    self.settings.remove_recent_file(&path);
+                    self.save_settings(&sender);
+                    self.update_recent_files_menu(&sender);
+                }
+            }
+            AppMsg::Proceed(GuardedAction::Close) => {
+                // Don't leave evremap running with the config under test
+                if let Some(mut test) = self.live_test.take() {
+                    test.stop();
                 }
+                // Stays the unmaximized size while the window is maximized
+                let (width, height) = root.default_size();
+                let window_settings = &mut self.settings.window;
+                window_settings.width = width;
+                window_settings.height = height;
+                window_settings.maximized = root.is_maximized();
+                self.save_settings(&sender);
+                root.destroy();
             }
-            AppMsg::OpenRequest => self.open_dialog.emit(OpenDialogMsg::Open),
-            AppMsg::OpenResponse(path) => match ConfigFile::read_from(path) {
-                Ok(config) => self.load(config),
+            AppMsg::SaveAndProceed(action) => {
+                self.after_save = Some(action);
+                sender.input(AppMsg::Save);
+            }
+            AppMsg::SetDirty(dirty) => self.dirty = dirty,
+            AppMsg::Undo => {
+                if let Some(previous) = self.undo_stack.pop() {
+                    let current = std::mem::replace(&mut self.history_snapshot, previous.clone());
+                    self.redo_stack.push(current);
+                    self.restore(previous);
+                }
+            }
+            AppMsg::Redo => {
+                if let Some(next) = self.redo_stack.pop() {
+                    let current = std::mem::replace(&mut self.history_snapshot, next.clone());
+                    self.undo_stack.push(current);
+                    self.restore(next);
+                }
+            }
+            AppMsg::RemapChanged => {
+                self.update_remap_conflicts();
+                self.mark_edited();
+            }
+            AppMsg::RemapCommentChanged => self.mark_text_edited(),
+            AppMsg::OpenResponse(path) if config_file::is_json_path(&path) => {
+                match ConfigFile::read_json(&path) {
+                    Ok(config) => self.open_loaded(path, config, None, false, &sender),
+                    Err(e) => {
+                        sender.input(AppMsg::err_msg(e, Some("Failed to open selected file")))
+                    }
+                }
+            }
+            AppMsg::OpenResponse(path) => match ConfigFile::read_with_source(&path) {
+                Ok((config, source)) => {
+                    self.open_loaded(path, config, Some(source), false, &sender)
+                }
+                Err(ConfigFileError::UnknownKeyNames(names)) => Self::ask_drop_unknown_keys(
+                    root,
+                    &sender,
+                    &names,
+                    AppMsg::OpenDroppingUnknownKeys(path),
+                ),
                 Err(e) => sender.input(AppMsg::err_msg(e, Some("Failed to open selected file"))),
             },
+            AppMsg::OpenDroppingUnknownKeys(path) => {
+                match ConfigFile::read_dropping_unknown_keys(&path) {
+                    // The file still has the keys, so there are changes to save right away
+                    Ok((config, source)) => {
+                        self.open_loaded(path, config, Some(source), true, &sender)
+                    }
+                    Err(e) => {
+                        sender.input(AppMsg::err_msg(e, Some("Failed to open selected file")))
+                    }
+                }
+            }
+            AppMsg::ImportRequest => self.import_dialog.emit(OpenDialogMsg::Open),
+            AppMsg::ImportResponse(path) => match ConfigFile::read_from(&path) {
+                Ok(config) => {
+                    self.show_config_warnings(&config);
+                    self.show_import_dialog(root, &sender, config)
+                }
+                Err(ConfigFileError::UnknownKeyNames(names)) => Self::ask_drop_unknown_keys(
+                    root,
+                    &sender,
+                    &names,
+                    AppMsg::ImportDroppingUnknownKeys(path),
+                ),
+                Err(e) => sender.input(AppMsg::err_msg(e, Some("Failed to open selected file"))),
+            },
+            AppMsg::ImportDroppingUnknownKeys(path) => {
+                match ConfigFile::read_dropping_unknown_keys(&path) {
+                    Ok((config, _)) => {
+                        self.show_config_warnings(&config);
+                        self.show_import_dialog(root, &sender, config)
+                    }
+                    Err(e) => {
+                        sender.input(AppMsg::err_msg(e, Some("Failed to open selected file")))
+                    }
+                }
+            }
+            AppMsg::ImportEntries { remaps, dual_role } => {
+                let existing_remaps = self.remaps_extract();
+                let existing_dual_role = self.dual_remaps_extract();
+                let duplicates = remaps
+                    .iter()
+                    .filter(|r| existing_remaps.iter().any(|e| e.same_as(r)))
+                    .count()
+                    + dual_role
+                        .iter()
+                        .filter(|d| existing_dual_role.iter().any(|e| e.same_as(d)))
+                        .count();
+                let imported = remaps.len() + dual_role.len();
+
+                let options = self.key_seq_options();
+                let mut remaps_guard = self.remaps.guard();
+                for remap in remaps {
+                    remaps_guard.push_back((remap, options.clone()));
+                }
+                drop(remaps_guard);
+                let mut dual_role_guard = self.dual_role_remaps.guard();
+                for dual_role_cfg in dual_role {
+                    dual_role_guard.push_back((dual_role_cfg, options.clone()));
+                }
+                drop(dual_role_guard);
+                self.update_observed_hints();
+                self.update_remap_conflicts();
+                self.mark_edited();
+
+                if duplicates > 0 {
+                    self.show_message_toast(format!(
+                        "Imported {imported} entries, {duplicates} of them already existed"
+                    ));
+                } else {
+                    self.show_message_toast(format!("Imported {imported} entries"));
+                }
+            }
             AppMsg::AddRemap => {
-                self.remaps.guard().push_back(RemapConfig::default());
+                let options = self.key_seq_options();
+                self.remaps
+                    .guard()
+                    .push_back((RemapConfig::default(), options));
+                self.update_observed_hints();
+                self.mark_edited();
+            }
+            AppMsg::AddCapturedRemap(keys) => {
+                let options = self.key_seq_options();
+                self.remaps.guard().push_back((
+                    RemapConfig {
+                        input: keys,
+                        ..Default::default()
+                    },
+                    options,
+                ));
+                self.update_observed_hints();
+                self.update_remap_conflicts();
+                self.mark_edited();
+                self.show_message_toast("Added a remap with the captured keys".to_owned());
+            }
+            AppMsg::AddKeyRemaps(keys) => {
+                let count = keys.len();
+                let options = self.key_seq_options();
+                let mut remaps_guard = self.remaps.guard();
+                for key in keys {
+                    remaps_guard.push_back((
+                        RemapConfig {
+                            input: vec![key],
+                            ..Default::default()
+                        },
+                        options.clone(),
+                    ));
+                }
+                drop(remaps_guard);
+                self.update_observed_hints();
+                self.update_remap_conflicts();
+                self.mark_edited();
+                self.show_message_toast(format!("Added {count} remaps, one for each picked key"));
             }
+            AppMsg::DeleteRemapRequest(idx) => self.confirm_destructive(
+                root,
+                &sender,
+                self.settings.confirmations.delete_entry,
+                "Delete this remap?",
+                self.remaps
+                    .get(idx.current_index())
+                    .map(|r| r.to_config().summary()),
+                AppMsg::DeleteRemap(idx),
+            ),
             AppMsg::DeleteRemap(idx) => {
                 let index = idx.current_index();
                 self.remaps.guard().remove(index);
+                self.update_remap_conflicts();
+                self.mark_edited();
+            }
+            AppMsg::ClearRemapsRequest => {
+                if !self.remaps.is_empty() {
+                    self.confirm_destructive(
+                        root,
+                        &sender,
+                        self.settings.confirmations.clear_all,
+                        "Delete all remaps?",
+                        Some(format!(
+                            "{} remaps will be deleted, this can be undone",
+                            self.remaps.len()
+                        )),
+                        AppMsg::ClearRemaps,
+                    )
+                }
+            }
+            AppMsg::ClearRemaps => {
+                self.remaps.guard().clear();
+                self.update_remap_conflicts();
+                self.mark_edited();
+            }
+            AppMsg::MoveRemap(idx, direction) => {
+                if move_entry(&mut self.remaps, &idx, direction) {
+                    self.mark_edited();
+                }
+            }
+            AppMsg::DuplicateRemap(idx) => {
+                let index = idx.current_index();
+                // The copy gets its own key sequence inputs built from the config
+                if let Some(remap) = self.remaps.get(index).map(RemapItem::to_config) {
+                    let options = self.key_seq_options();
+                    self.remaps.guard().insert(index + 1, (remap, options));
+                    self.update_observed_hints();
+                    self.update_remap_conflicts();
+                    self.mark_edited();
+                }
+            }
+            AppMsg::DuplicateDualRoleRemap(idx) => {
+                let index = idx.current_index();
+                if let Some(dual_role) = self
+                    .dual_role_remaps
+                    .get(index)
+                    .map(DualRoleMapItem::to_config)
+                {
+                    let options = self.key_seq_options();
+                    self.dual_role_remaps
+                        .guard()
+                        .insert(index + 1, (dual_role, options));
+                    self.update_observed_hints();
+                    self.mark_edited();
+                }
+            }
+            AppMsg::MoveDualRoleRemap(idx, direction) => {
+                if move_entry(&mut self.dual_role_remaps, &idx, direction) {
+                    self.mark_edited();
+                }
+            }
+            AppMsg::DualRoleRemapChanged => self.mark_edited(),
+            AppMsg::FocusRemap(direction) => self.focus_remap(root, direction),
+            AppMsg::SetRemapsExpanded(expanded) => {
+                self.remaps.broadcast(RemapItemMsg::SetExpanded(expanded))
             }
             AppMsg::AddDualRoleRemap => {
+                let options = self.key_seq_options();
                 self.dual_role_remaps
                     .guard()
-                    .push_back(DualRoleConfig::default());
+                    .push_back((DualRoleConfig::default(), options));
+                self.update_observed_hints();
+                self.mark_edited();
             }
+            AppMsg::DeleteDualRoleRemapRequest(idx) => self.confirm_destructive(
+                root,
+                &sender,
+                self.settings.confirmations.delete_entry,
+                "Delete this dual-role entry?",
+                self.dual_role_remaps
+                    .get(idx.current_index())
+                    .map(|d| d.to_config().summary()),
+                AppMsg::DeleteDualRoleRemap(idx),
+            ),
             AppMsg::DeleteDualRoleRemap(idx) => {
                 let index = idx.current_index();
                 self.dual_role_remaps.guard().remove(index);
+                self.mark_edited();
+            }
+            AppMsg::ClearDualRoleRequest => {
+                if !self.dual_role_remaps.is_empty() {
+                    self.confirm_destructive(
+                        root,
+                        &sender,
+                        self.settings.confirmations.clear_all,
+                        "Delete all dual-role entries?",
+                        Some(format!(
+                            "{} dual-role entries will be deleted, this can be undone",
+                            self.dual_role_remaps.len()
+                        )),
+                        AppMsg::ClearDualRole,
+                    )
+                }
+            }
+            AppMsg::ClearDualRole => {
+                self.dual_role_remaps.guard().clear();
+                self.mark_edited();
             }
             AppMsg::SetDevice(dev) => {
-                self.show_message_toast(format!("Selected config device \"{}\"", dev.name));
+                let shared_name = self.duplicate_names.contains(&dev.name);
+                let note = match (&dev.phys, shared_name) {
+                    (_, false) => "",
+                    (Some(_), true) => {
+                        ", keep the phys to tell it apart from other devices with the same name"
+                    }
+                    (None, true) => {
+                        ", it has no phys and other devices have the same name, evremap may pick any of them"
+                    }
+                };
+                self.show_message_toast(format!(
+                    "Selected config device \"{}\"{note}",
+                    dev.display_name()
+                ));
                 self.config.name.set_text(dev.name);
-                if let Some(devphys) = dev.phys {
-                    self.config.phys.set_text(devphys);
+                // Don't leave the phys of a previously selected device behind
+                match dev.phys {
+                    Some(phys) => self.config.phys.set_text(phys),
+                    None => self.config.phys.delete_text(0, None),
                 }
             }
+            AppMsg::UseMyKeyboardRequest => sender.spawn_oneshot_command(|| {
+                CommandMsg::KeyboardCandidates(DeviceInfo::obtain_device_list().map(|scan| {
+                    scan.devices
+                        .into_iter()
+                        .filter(|d| d.is_keyboard && d.supports_remap && !d.is_virtual)
+                        .collect()
+                }))
+            }),
+            AppMsg::UseDevice(dev) => self.confirm_destructive(
+                root,
+                &sender,
+                !self.config.name_text().is_empty() || !self.config.phys_text().is_empty(),
+                "Replace the device name and phys?",
+                None,
+                AppMsg::SetDevice(dev),
+            ),
+            AppMsg::FindDevicesByPattern => {
+                self.device_picker
+                    .emit(DevicePickerMsg::FindByPattern(self.devices.clone()));
+                self.device_picker.widget().present(Some(root));
+            }
             AppMsg::RefreshDevices { is_initial } => {
+                // Don't start overlapping scans
+                if self.refreshing {
+                    return;
+                }
+                self.refreshing = true;
                 sender.spawn_oneshot_command(move || match DeviceInfo::obtain_device_list() {
-                    Ok(devices) => CommandMsg::UpdateDeviceList {
-                        devices,
-                        is_initial,
-                    },
+                    Ok(scan) => CommandMsg::UpdateDeviceList { scan, is_initial },
                     Err(e) => CommandMsg::DeviceListRefreshError(Box::new(e)),
                 });
             }
+            AppMsg::ViewConfigDeviceEvents => {
+                if let [dev] = self.config_devices().as_slice() {
+                    sender.input(AppMsg::SetLoggerDevice((*dev).clone()));
+                }
+            }
             AppMsg::SetLoggerDevice(dev) => {
-                self.show_message_toast(format!("Selected event logging device \"{}\"", dev.name));
+                self.show_message_toast(format!(
+                    "Selected event logging device \"{}\"",
+                    dev.display_name()
+                ));
+                self.settings.last_logger_device = Some(DeviceId::of(&dev));
+                self.save_settings(&sender);
                 self.event_logger.emit(EventLoggerMsg::SetDevice(dev));
             }
+            AppMsg::KeyObserved(dev, key) => {
+                self.observed_keys.entry(dev).or_default().insert(key);
+                self.update_observed_hints();
+            }
+            AppMsg::LoggedKeyEvent {
+                device,
+                key,
+                pressed,
+                time,
+            } => {
+                // Keys of other devices aren't affected by the config
+                if self.is_config_device(&device.name, device.phys.as_deref()) {
+                    self.dual_role_remaps
+                        .broadcast(DualRoleMapItemMsg::LoggedKeyEvent { key, pressed, time });
+                }
+            }
+            AppMsg::LogExported(path) => {
+                self.show_message_toast(format!("Exported event log to {}", path.display()))
+            }
+            AppMsg::ConfigDeviceChanged => {
+                self.update_observed_hints();
+                self.mark_text_edited();
+            }
+            AppMsg::LoggerDeviceCleared => {
+                self.settings.last_logger_device = None;
+                self.save_settings(&sender);
+            }
+            AppMsg::StartTest => {
+                if self.live_test.is_some() {
+                    return;
+                }
+                match LiveTest::start(&self.to_config_file()) {
+                    Ok((test, stderr)) => {
+                        self.live_test = Some(test);
+                        self.test_generation += 1;
+                        self.test_output_shown = true;
+                        self.test_log.set_text("");
+                        let generation = self.test_generation;
+                        sender.spawn_command(move |cmd_sender| {
+                            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                                if cmd_sender.send(CommandMsg::TestOutput(line)).is_err() {
+                                    return;
+                                }
+                            }
+                            let _ = cmd_sender.send(CommandMsg::TestFinished(generation));
+                        });
+                    }
+                    Err(e) => sender.input(AppMsg::err_msg(e, Some("Failed to test the config"))),
+                }
+            }
+            AppMsg::StopTest => {
+                if let Some(test) = &mut self.live_test {
+                    test.stop();
+                }
+            }
             AppMsg::ReportError {
                 error,
                 extra_context,
             } => self.show_error_toast(error, extra_context),
-            AppMsg::ShowHiddenDevices => {
-                self.device_browser.broadcast(DeviceDisplayMsg::ShowHidden)
+            AppMsg::SetDeviceKindFilter(kind) => {
+                self.device_filter.kind = kind;
+                self.apply_device_filter();
+            }
+            AppMsg::SetShowVirtual(show) => {
+                self.device_filter.show_virtual = show;
+                self.apply_device_filter();
+            }
+            AppMsg::FilterDevices(text) => {
+                self.device_filter.text = text;
+                self.apply_device_filter();
+            }
+            AppMsg::SetDeviceSort(sort) => {
+                if self.device_sort != sort {
+                    self.device_sort = sort;
+                    self.rebuild_device_browser();
+                }
+            }
+            AppMsg::EmergencyStop => {
+                self.event_logger.emit(EventLoggerMsg::StopLogging);
+                if let Some(mut test) = self.live_test.take() {
+                    test.stop();
+                    self.append_test_log("evremap was stopped");
+                    self.show_message_toast("Stopped event logging and the config test".to_owned());
+                } else {
+                    self.show_message_toast("Stopped all event logging".to_owned());
+                }
+            }
+            AppMsg::SetDeviceAlias { id, alias } => {
+                self.settings.set_device_alias(id, alias);
+                self.save_settings(&sender);
+            }
+            AppMsg::OpenPreferences => self.preferences.widget().present(Some(root)),
+            AppMsg::OpenDocs(topic) => Self::open_docs(root, &sender, topic),
+            AppMsg::SettingsChanged(settings) => {
+                let key_codes_changed = settings.hide_key_codes != self.settings.hide_key_codes;
+                if settings.event_logger.max_lines != self.settings.event_logger.max_lines {
+                    self.event_logger
+                        .emit(EventLoggerMsg::SetMaxLines(settings.event_logger.max_lines));
+                }
+                self.settings = settings;
+                self.save_settings(&sender);
+                self.update_hotplug_watch(&sender);
+                if key_codes_changed {
+                    self.event_logger.emit(EventLoggerMsg::SetShowKeyCodes(
+                        !self.settings.hide_key_codes,
+                    ));
+                    let options = self.key_seq_options();
+                    self.remaps
+                        .broadcast(RemapItemMsg::SetKeySeqOptions(options.clone()));
+                    self.dual_role_remaps
+                        .broadcast(DualRoleMapItemMsg::SetKeySeqOptions(options));
+                }
+            }
+            AppMsg::LogMaxLinesChanged(max_lines) => {
+                if self.settings.event_logger.max_lines != max_lines {
+                    self.settings.event_logger.max_lines = max_lines;
+                    self.save_settings(&sender);
+                }
+            }
+            AppMsg::KeyPreset(change) => {
+                match change {
+                    KeyPresetChange::Save(preset) => {
+                        self.show_message_toast(format!("Saved preset \"{}\"", preset.name));
+                        self.settings.save_key_preset(preset);
+                    }
+                    KeyPresetChange::Remove(name) => self.settings.remove_key_preset(&name),
+                }
+                let options = self.key_seq_options();
+                self.remaps
+                    .broadcast(RemapItemMsg::SetKeySeqOptions(options.clone()));
+                self.dual_role_remaps
+                    .broadcast(DualRoleMapItemMsg::SetKeySeqOptions(options));
+                self.save_settings(&sender);
+            }
+            AppMsg::DeviceFieldCopied(field) => self.show_message_toast(format!(
+                "Copied the device {} to the clipboard",
+                field.label()
+            )),
+            AppMsg::PreviewConfig => match self
+                .to_config_file()
+                .to_toml(self.config_source.as_ref())
+            {
+                Ok(contents) => {
+                    self.config_preview
+                        .emit(ConfigPreviewMsg::SetContents(contents));
+                    self.config_preview.widget().present(Some(root));
+                }
+                Err(e) => sender.input(AppMsg::err_msg(e, Some("Failed to generate the config"))),
+            },
+            AppMsg::RevertRequest => match self.current_path.clone() {
+                Some(path) if self.dirty => self.confirm_destructive(
+                    root,
+                    &sender,
+                    self.settings.confirmations.revert,
+                    "Revert to the saved file?",
+                    Some("Unsaved changes will be lost, this can't be undone".to_owned()),
+                    AppMsg::OpenResponse(path),
+                ),
+                Some(_) => self.show_message_toast("There are no unsaved changes".to_owned()),
+                None => self.show_message_toast("The config hasn't been saved yet".to_owned()),
+            },
+            AppMsg::ShowChanges => match self.current_path.clone() {
+                Some(path) => {
+                    let config = self.to_config_file();
+                    let generated = if config_file::is_json_path(&path) {
+                        config.to_json()
+                    } else {
+                        config.to_toml(self.config_source.as_ref())
+                    };
+                    let contents =
+                        generated.and_then(|new| Ok((std::fs::read_to_string(&path)?, new)));
+                    match contents {
+                        Ok((old, new)) if old == new => self.show_message_toast(format!(
+                            "Saving would not change {}",
+                            path.display()
+                        )),
+                        Ok((old, new)) => {
+                            self.changes_dialog
+                                .emit(ChangesMsg::SetChanges { path, old, new });
+                            self.changes_dialog.widget().present(Some(root));
+                        }
+                        Err(e) => sender.input(AppMsg::err_msg(
+                            e,
+                            Some("Failed to compare the config with the saved file"),
+                        )),
+                    }
+                }
+                None => self.show_message_toast(
+                    "The config isn't saved to a file yet, there is nothing to compare with"
+                        .to_owned(),
+                ),
+            },
+            AppMsg::ShowAbout => {
+                sender.spawn_oneshot_command(|| CommandMsg::ShowAbout(live_test::evremap_version()))
             }
-            AppMsg::HideUselessDevices => {
-                self.device_browser.broadcast(DeviceDisplayMsg::HideUseless)
+            AppMsg::PageChanged(page) => {
+                // Pages removed in editor-only mode shouldn't make the editor the remembered one
+                if !self.editor_only && self.settings.window.page.as_ref() != Some(&page) {
+                    self.settings.window.page = Some(page);
+                    self.save_settings(&sender);
+                }
             }
         }
     }
@@ -518,28 +1870,413 @@ impl Component for AppModel {
         &mut self,
         message: Self::CommandOutput,
         sender: ComponentSender<Self>,
-        _root: &Self::Root,
+        root: &Self::Root,
     ) {
         match message {
-            CommandMsg::UpdateDeviceList {
-                devices,
-                is_initial,
-            } => {
+            CommandMsg::TestOutput(line) => self.append_test_log(&line),
+            CommandMsg::TestFinished(generation) => {
+                if generation != self.test_generation {
+                    return;
+                }
+                if let Some(mut test) = self.live_test.take() {
+                    if test.was_stopped() {
+                        self.append_test_log("evremap was stopped");
+                    } else {
+                        match test.wait() {
+                            Ok(status) => {
+                                self.append_test_log(&format!("evremap exited: {status}"))
+                            }
+                            Err(e) => self.append_test_log(&format!(
+                                "Failed to get the exit status of evremap: {e}"
+                            )),
+                        }
+                        self.show_message_toast(
+                            "evremap exited, see its output for details".to_owned(),
+                        );
+                    }
+                }
+            }
+            CommandMsg::PrivilegedSaveFinished {
+                path,
+                config,
+                result,
+            } => match result {
+                Ok(()) => self.config_saved(path, &config, &sender),
+                Err(e) => {
+                    self.after_save = None;
+                    sender.input(AppMsg::err_msg(
+                        e,
+                        Some("Failed to save config file as administrator"),
+                    ))
+                }
+            },
+            CommandMsg::ShowAbout(evremap_version) => Self::show_about(root, evremap_version),
+            CommandMsg::UpdateDeviceList { scan, is_initial } => {
+                self.refreshing = false;
+                self.show_scan_status(&scan, &sender);
+                match scan.permission_denied_count() {
+                    0 => {}
+                    count => self.show_message_toast(format!(
+                        "{count} devices couldn't be opened due to missing permissions. Join the \
+                         \"input\" group or run as root to see them"
+                    )),
+                }
+                let devices = scan.devices;
+                if is_initial {
+                    let browser_settings = self.settings.device_browser;
+                    self.device_sort = browser_settings.sort;
+                    // These aren't settings, keep what the user has chosen
+                    self.device_filter = DeviceFilter {
+                        show_virtual: self.device_filter.show_virtual,
+                        text: std::mem::take(&mut self.device_filter.text),
+                        ..browser_settings.filter()
+                    };
+                    // The path may be different now, so look the device up by its identity
+                    if let Some(dev) = self
+                        .settings
+                        .last_logger_device
+                        .as_ref()
+                        .and_then(|id| devices.iter().find(|d| id.matches(d)))
+                    {
+                        let mut dev = dev.clone();
+                        dev.alias = self
+                            .settings
+                            .device_alias(&DeviceId::of(&dev))
+                            .map(str::to_owned);
+                        self.event_logger.emit(EventLoggerMsg::SetDevice(dev));
+                    }
+                }
                 self.update_duplicate_names(&devices);
-                self.update_device_list(devices);
-                if !is_initial {
-                    self.show_message_toast("Device list updated".to_owned());
+                self.devices = devices;
+                self.rebuild_device_browser();
+                self.update_observed_hints();
+            }
+            CommandMsg::KeyboardCandidates(Ok(mut keyboards)) => {
+                for dev in &mut keyboards {
+                    dev.alias = self
+                        .settings
+                        .device_alias(&DeviceId::of(dev))
+                        .map(str::to_owned);
+                }
+                match keyboards.len() {
+                    0 => self.show_message_toast("No connected keyboards found".to_owned()),
+                    1 => sender.input(AppMsg::UseDevice(keyboards.remove(0))),
+                    _ => {
+                        self.device_picker
+                            .emit(DevicePickerMsg::ChooseKeyboard(keyboards));
+                        self.device_picker.widget().present(Some(root));
+                    }
                 }
             }
-            CommandMsg::DeviceListRefreshError(e) => sender.input(AppMsg::ReportError {
-                error: e,
-                extra_context: Some("Failed to refresh the device list".to_owned()),
-            }),
+            CommandMsg::KeyboardCandidates(Err(e)) => sender.input(AppMsg::err_msg(
+                e,
+                Some("Failed to look for connected keyboards"),
+            )),
+            CommandMsg::ClearScanStatus(generation) => {
+                if generation == self.scan_status_generation {
+                    self.scan_status = None;
+                    self.scan_status_details = None;
+                }
+            }
+            CommandMsg::DevicesHotplugged => {
+                sender.input(AppMsg::RefreshDevices { is_initial: false })
+            }
+            CommandMsg::DeviceListRefreshError(e) => {
+                self.refreshing = false;
+                sender.input(AppMsg::ReportError {
+                    error: e,
+                    extra_context: Some("Failed to refresh the device list".to_owned()),
+                })
+            }
         }
     }
 }
 
 impl AppModel {
+    /// Write the config to `path` and remember it as the current file
+    fn save_config(&mut self, path: PathBuf, sender: &ComponentSender<Self>) {
+        let backup_result = if self.settings.saving.backup {
+            config_file::backup_existing(&path)
+        } else {
+            Ok(None)
+        };
+        let is_json = config_file::is_json_path(&path);
+        let config = self.to_config_file();
+        let result = if is_json {
+            config.write_json(&path)
+        } else {
+            config.save_to_preserving(&path, self.config_source.as_ref())
+        };
+        match result {
+            Ok(()) => {
+                // A failed backup shouldn't cost the user their edits
+                if let Err(e) = backup_result {
+                    sender.input(AppMsg::err_msg(
+                        e,
+                        Some("Saved the config, but failed to back up the previous version"),
+                    ));
+                }
+                self.config_saved(path, &config, sender)
+            }
+            // Most configs live in /etc, offer to write it as root instead. The backup is made
+            // as root too then. JSON files are only for other tools, so they never need it.
+            Err(e) if e.is_permission_denied() && !is_json => {
+                sender.input(AppMsg::PrivilegedSaveRequest(path))
+            }
+            Err(e) => {
+                self.after_save = None;
+                sender.input(AppMsg::err_msg(e, Some("Failed to save config file")))
+            }
+        }
+    }
+
+    /// Remember the file `saved` was written to and continue with the pending action. Saving as
+    /// administrator doesn't block the editor, so the config may have been edited since.
+    fn config_saved(&mut self, path: PathBuf, saved: &ConfigFile, sender: &ComponentSender<Self>) {
+        self.show_info_toast(format!("Saved to {}", path.display()));
+        self.remember_recent_file(path.clone(), sender);
+        self.current_path = Some(path);
+        self.saved_config = Some(saved.clone());
+        let current = self.to_config_file();
+        self.update_dirty(&current);
+        // Don't close or replace the config with the newer edits unsaved
+        if let Some(action) = self.after_save.take()
+            && !self.dirty
+        {
+            sender.input(AppMsg::Proceed(action));
+        }
+    }
+
+    /// Show a config read from `path` in the editor. `dirty` is set if the config differs from
+    /// the file.
+    fn open_loaded(
+        &mut self,
+        path: PathBuf,
+        config: ConfigFile,
+        source: Option<ConfigSource>,
+        dirty: bool,
+        sender: &ComponentSender<Self>,
+    ) {
+        self.show_loaded_summary(&path, &config, source.as_ref());
+        self.config_source = source;
+        self.remember_recent_file(path.clone(), sender);
+        self.current_path = Some(path);
+        self.show_config_warnings(&config);
+        self.load(config);
+        self.reset_history();
+        self.saved_config = (!dirty).then(|| self.history_snapshot.clone());
+        self.update_observed_hints();
+        self.update_remap_conflicts();
+        // Setting the device fields queues change notifications, this has to come after them
+        sender.input(AppMsg::SetDirty(dirty));
+    }
+
+    /// Offer to read a config again without the keys this system doesn't know, by sending
+    /// `proceed`
+    fn ask_drop_unknown_keys(
+        root: &gtk::Window,
+        sender: &ComponentSender<Self>,
+        names: &[String],
+        proceed: AppMsg,
+    ) {
+        let dialog = adw::AlertDialog::new(
+            Some("Unknown keys"),
+            Some(&format!(
+                "The config uses keys this system doesn't know, it may have been written for a \
+                 newer kernel: {}. They can be left out, dual-role entries triggered by one of \
+                 them are left out entirely.",
+                names.join(", ")
+            )),
+        );
+        dialog.add_responses(&[("cancel", "Cancel"), ("drop", "Leave Out Unknown Keys")]);
+        dialog.set_response_appearance("drop", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+        let sender = sender.clone();
+        dialog.choose(root, None::<&gtk::gio::Cancellable>, move |response| {
+            if response == "drop" {
+                sender.input(proceed);
+            }
+        });
+    }
+
+    /// Ask whether to retry saving to a file the user can't write with administrator rights
+    fn ask_privileged_save(root: &gtk::Window, sender: &ComponentSender<Self>, path: PathBuf) {
+        let dialog = adw::AlertDialog::new(
+            Some("Save as administrator?"),
+            Some(&format!(
+                "You are not allowed to write to {}. It can be saved with administrator rights \
+                 after authenticating.",
+                path.display()
+            )),
+        );
+        dialog.add_responses(&[
+            ("cancel", "Cancel"),
+            ("privileged", "Save as Administrator"),
+        ]);
+        dialog.set_response_appearance("privileged", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("privileged"));
+        dialog.set_close_response("cancel");
+        let sender = sender.clone();
+        dialog.choose(root, None::<&gtk::gio::Cancellable>, move |response| {
+            if response == "privileged" {
+                sender.input(AppMsg::PrivilegedSave(path));
+            } else {
+                sender.input(AppMsg::SaveCancelled);
+            }
+        });
+    }
+
+    fn show_about(root: &gtk::Window, evremap_version: Option<String>) {
+        let evremap_version = evremap_version.unwrap_or_else(|| "evremap was not found".into());
+        let repository = env!("CARGO_PKG_REPOSITORY");
+        let dialog = adw::AboutDialog::builder()
+            .application_name("evremap config editor")
+            .application_icon(APP_ID)
+            .version(env!("CARGO_PKG_VERSION"))
+            .comments(format!(
+                "Graphical editor for evremap config files\n{evremap_version}"
+            ))
+            .website(repository)
+            .issue_url(format!("{repository}/issues"))
+            .debug_info(format!(
+                "{APP_ID} {}\n{evremap_version}",
+                env!("CARGO_PKG_VERSION")
+            ))
+            .build();
+        let license = env!("CARGO_PKG_LICENSE");
+        if !license.is_empty() {
+            dialog.set_license(license);
+        }
+        dialog.add_acknowledgement_section(
+            Some("Remapping by"),
+            &["evremap https://github.com/wez/evremap"],
+        );
+        dialog.present(Some(root));
+    }
+
+    /// Highlight the entries evremap would reject or ignore and ask whether to save anyway
+    fn check_before_save(
+        &self,
+        root: &gtk::Window,
+        sender: &ComponentSender<Self>,
+        target: SaveTarget,
+    ) {
+        let problems = self.to_config_file().save_problems();
+        for idx in 0..self.remaps.len() {
+            self.remaps.send(
+                idx,
+                RemapItemMsg::SetSaveProblem(problems.remaps.contains(&idx)),
+            );
+        }
+        for idx in 0..self.dual_role_remaps.len() {
+            self.dual_role_remaps.send(
+                idx,
+                DualRoleMapItemMsg::SetSaveProblem(problems.dual_role.contains(&idx)),
+            );
+        }
+        if problems.is_empty() {
+            sender.input(AppMsg::SaveChecked(target));
+            return;
+        }
+        let dialog = adw::AlertDialog::new(
+            Some("Save with problems?"),
+            Some(&problems.descriptions.join("\n")),
+        );
+        dialog.add_responses(&[("cancel", "Cancel"), ("save", "Save Anyway")]);
+        dialog.set_response_appearance("save", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+        let sender = sender.clone();
+        dialog.choose(root, None::<&gtk::gio::Cancellable>, move |response| {
+            if response == "save" {
+                sender.input(AppMsg::SaveChecked(target));
+            } else {
+                sender.input(AppMsg::SaveCancelled);
+            }
+        });
+    }
+
+    /// Ask whether to save or discard the unsaved changes before doing something that would lose
+    /// them
+    fn guard_unsaved(
+        &self,
+        root: &gtk::Window,
+        sender: &ComponentSender<Self>,
+        action: GuardedAction,
+    ) {
+        let discard_silently =
+            matches!(action, GuardedAction::Close) && !self.settings.confirmations.discard_on_close;
+        if !self.dirty || discard_silently {
+            sender.input(AppMsg::Proceed(action));
+            return;
+        }
+        let dialog = adw::AlertDialog::new(
+            Some("Save changes?"),
+            Some("The config has unsaved changes that will be lost otherwise"),
+        );
+        dialog.add_responses(&[
+            ("cancel", "Cancel"),
+            ("discard", "Discard"),
+            ("save", "Save"),
+        ]);
+        dialog.set_response_appearance("discard", adw::ResponseAppearance::Destructive);
+        dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("save"));
+        dialog.set_close_response("cancel");
+        let sender = sender.clone();
+        dialog.choose(
+            root,
+            None::<&gtk::gio::Cancellable>,
+            move |response| match response.as_str() {
+                "discard" => sender.input(AppMsg::Proceed(action)),
+                "save" => sender.input(AppMsg::SaveAndProceed(action)),
+                _ => {}
+            },
+        );
+    }
+
+    /// Open the documentation in the default browser. If there's no browser to open it with,
+    /// the link is copied to the clipboard instead.
+    fn open_docs(root: &gtk::Window, sender: &ComponentSender<Self>, topic: DocsTopic) {
+        let uri = topic.url();
+        let sender = sender.clone();
+        let window = root.clone();
+        gtk::UriLauncher::new(uri).launch(
+            Some(root),
+            None::<&gtk::gio::Cancellable>,
+            move |result| {
+                if let Err(e) = result {
+                    window.clipboard().set_text(uri);
+                    sender.input(AppMsg::err_msg(
+                        e,
+                        Some(format!(
+                            "Failed to open {uri}, the link was copied to the clipboard"
+                        )),
+                    ));
+                }
+            },
+        );
+    }
+
+    /// Show the scan summary in the device browser for a few seconds
+    fn show_scan_status(&mut self, scan: &DeviceScan, sender: &ComponentSender<Self>) {
+        self.scan_status_generation = self.scan_status_generation.wrapping_add(1);
+        self.scan_status = Some(scan.summary());
+        self.scan_status_details = (!scan.skipped.is_empty()).then(|| {
+            scan.skipped
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
+        let generation = self.scan_status_generation;
+        sender.spawn_oneshot_command(move || {
+            std::thread::sleep(SCAN_STATUS_TIMEOUT);
+            CommandMsg::ClearScanStatus(generation)
+        });
+    }
+
     /// Update the list of device names that have multiple devices associated with them
     fn update_duplicate_names(&mut self, devices: &[DeviceInfo]) {
         let names_counts: HashMap<&str, usize> =
@@ -559,13 +2296,216 @@ impl AppModel {
         );
     }
 
-    /// Clear the device browser list and add each device
-    fn update_device_list(&mut self, devices: Vec<DeviceInfo>) {
+    /// Fill the device browser from the devices of the last scan in the selected order, without
+    /// rescanning them
+    fn rebuild_device_browser(&mut self) {
+        self.device_sort.sort(&mut self.devices);
+        for dev in &mut self.devices {
+            dev.alias = self
+                .settings
+                .device_alias(&DeviceId::of(dev))
+                .map(str::to_owned);
+        }
+        // Devices sharing a name are grouped at the position of the first one
+        let mut groups: Vec<Vec<DeviceInfo>> = Vec::new();
+        let mut group_of_name: HashMap<&str, usize> = HashMap::new();
+        for dev in &self.devices {
+            match group_of_name.get(dev.name.as_str()) {
+                Some(&idx) => groups[idx].push(dev.clone()),
+                None => {
+                    group_of_name.insert(&dev.name, groups.len());
+                    groups.push(vec![dev.clone()]);
+                }
+            }
+        }
         let mut device_list = self.device_browser.guard();
         device_list.clear();
-        for dev in devices {
-            device_list.push_back(dev);
+        for group in groups {
+            device_list.push_back((group, self.device_filter.clone()));
+        }
+    }
+
+    fn remember_recent_file(&mut self, path: PathBuf, sender: &ComponentSender<Self>) {
+        self.settings.add_recent_file(path);
+        self.save_settings(sender);
+        self.update_recent_files_menu(sender);
+    }
+
+    /// Fill the recent files menu with a button for each file
+    fn update_recent_files_menu(&self, sender: &ComponentSender<Self>) {
+        while let Some(child) = self.recent_files_box.first_child() {
+            self.recent_files_box.remove(&child);
+        }
+        for path in &self.settings.recent_files {
+            let button = gtk::Button::with_label(&path.display().to_string());
+            button.set_has_frame(false);
+            let sender = sender.clone();
+            let path = path.clone();
+            button.connect_clicked(move |btn| {
+                if let Some(popover) = btn
+                    .ancestor(gtk::Popover::static_type())
+                    .and_downcast::<gtk::Popover>()
+                {
+                    popover.popdown();
+                }
+                sender.input(AppMsg::OpenRecentRequest(path.clone()));
+            });
+            self.recent_files_box.append(&button);
+        }
+    }
+
+    /// Start or stop watching for devices being plugged in or removed to match the "Refresh on
+    /// hotplug" setting. The devices can always be refreshed manually, also if udev can't be
+    /// watched.
+    fn update_hotplug_watch(&mut self, sender: &ComponentSender<Self>) {
+        let wanted = !self.editor_only && self.settings.device_browser.auto_refresh;
+        if !wanted {
+            // Dropping the sender stops the watch
+            self.hotplug_watch = None;
+        } else if self.hotplug_watch.is_none() {
+            let (stop_sender, stop) = mpsc::channel();
+            self.hotplug_watch = Some(stop_sender);
+            sender.spawn_command(move |cmd_sender| {
+                let res = DeviceInfo::watch_hotplug(stop, || {
+                    cmd_sender.send(CommandMsg::DevicesHotplugged).is_ok()
+                });
+                if let Err(e) = res {
+                    log::warn!("Device hotplug monitoring stopped: {e}");
+                }
+            });
+        }
+    }
+
+    /// Settings the key sequence inputs of the entries depend on
+    fn key_seq_options(&self) -> KeySeqOptions {
+        KeySeqOptions {
+            presets: self.settings.key_presets.clone(),
+            show_key_codes: !self.settings.hide_key_codes,
+        }
+    }
+
+    /// Write the settings to disk and let the preferences dialog know about the changes made
+    /// elsewhere
+    fn save_settings(&self, sender: &ComponentSender<Self>) {
+        self.preferences
+            .emit(PreferencesMsg::Sync(self.settings.clone()));
+        match self.settings.save() {
+            // Already reported when loading them
+            Ok(()) | Err(SettingsError::SchemaMissing) => {}
+            Err(e) => sender.input(AppMsg::err_msg(e, Some("Failed to save settings"))),
+        }
+    }
+
+    /// Keys observed in the event logger from the devices matching the editor's name and phys.
+    /// [`None`] if none of them were logged this session.
+    fn config_device_observed_keys(&self) -> Option<HashSet<KeyCode>> {
+        self.observed_keys
+            .iter()
+            .filter(|(id, _)| self.is_config_device(&id.name, id.phys.as_deref()))
+            .map(|(_, keys)| keys)
+            .fold(None, |acc: Option<HashSet<KeyCode>>, keys| {
+                let mut acc = acc.unwrap_or_default();
+                acc.extend(keys);
+                Some(acc)
+            })
+    }
+
+    /// Keys supported by the config's device that weren't observed from it in the event logger.
+    /// [`None`] if the device isn't connected or wasn't logged this session.
+    fn config_device_unseen_keys(&self) -> Option<HashSet<KeyCode>> {
+        let observed = self.config_device_observed_keys()?;
+        let supported = self.config_device_supported_keys()?;
+        Some(
+            supported
+                .into_iter()
+                .filter(|k| !observed.contains(k))
+                .collect(),
+        )
+    }
+
+    /// Keys supported by the connected device matching the editor's name and phys. [`None`] if
+    /// there's no such device.
+    fn config_device_supported_keys(&self) -> Option<Vec<KeyCode>> {
+        self.config_devices().first().map(|dev| dev.keys.clone())
+    }
+
+    /// Connected devices matching the editor's name, and phys if it's filled in. evremap picks
+    /// its device the same way.
+    fn config_devices(&self) -> Vec<&DeviceInfo> {
+        self.devices
+            .iter()
+            .filter(|dev| self.is_config_device(&dev.name, dev.phys.as_deref()))
+            .collect()
+    }
+
+    /// Whether evremap would pick the device with the config in the editor: the name has to be
+    /// the same, and the phys too if it's filled in
+    fn is_config_device(&self, name: &str, phys: Option<&str>) -> bool {
+        let config_phys = self.config.phys_text();
+        self.config.name_text() == name
+            && (config_phys.is_empty() || phys == Some(config_phys.as_str()))
+    }
+
+    /// Which connected devices the config would apply to, and whether that's exactly one.
+    /// [`None`] if the name isn't filled in or the devices aren't scanned.
+    fn device_match_status(&self) -> Option<(String, bool)> {
+        // Until the first scan finishes, nothing can be said about the connected devices
+        let scanning = self.refreshing && self.devices.is_empty();
+        if self.editor_only || scanning || self.config.name_text().is_empty() {
+            return None;
         }
+        let status = match self.config_devices().as_slice() {
+            // Not an error, evremap waits for the device to be plugged in
+            [] => (
+                "No matching device currently connected, the match can't be verified until it is \
+                 plugged in"
+                    .to_owned(),
+                false,
+            ),
+            [dev] => (format!("Matches: {}", dev.path.display()), true),
+            devices if self.config.phys_text().is_empty() => (
+                format!(
+                    "Matches {} devices, specifying the phys is recommended",
+                    devices.len()
+                ),
+                false,
+            ),
+            devices => (format!("Matches {} devices", devices.len()), false),
+        };
+        Some(status)
+    }
+
+    /// Let the entries know which keys the config's device supports, and which of those were
+    /// never seen from it to hint at them
+    fn update_observed_hints(&self) {
+        let unseen = self.config_device_unseen_keys();
+        self.remaps
+            .broadcast(RemapItemMsg::SetUnseenKeys(unseen.clone()));
+        self.remaps.broadcast(RemapItemMsg::SetSupportedKeys(
+            self.config_device_supported_keys(),
+        ));
+        self.dual_role_remaps
+            .broadcast(DualRoleMapItemMsg::SetUnseenKeys(unseen));
+    }
+
+    /// Highlight remaps that share their input keys with another remap
+    fn update_remap_conflicts(&self) {
+        let remaps = self.remaps_extract();
+        for (idx, remap) in remaps.iter().enumerate() {
+            let conflicting = !remap.input.is_empty()
+                && remaps
+                    .iter()
+                    .enumerate()
+                    .any(|(other_idx, other)| other_idx != idx && other.same_input(remap));
+            self.remaps
+                .send(idx, RemapItemMsg::SetConflict(conflicting));
+        }
+    }
+
+    /// Show and hide the devices in the browser, the list itself stays the same
+    fn apply_device_filter(&self) {
+        self.device_browser
+            .broadcast(DeviceGroupMsg::SetFilter(self.device_filter.clone()));
     }
 
     /// Load config data from a parsed config file
@@ -578,17 +2518,160 @@ impl AppModel {
             remap: config_remap,
         } = config_file;
 
+        let options = self.key_seq_options();
         let mut remaps = self.remaps.guard();
         remaps.clear();
         for remap in config_remap {
-            remaps.push_back(remap);
+            remaps.push_back((remap, options.clone()));
         }
 
         let mut dual_role = self.dual_role_remaps.guard();
         dual_role.clear();
         for dual_role_cfg in config_dual_role {
-            dual_role.push_back(dual_role_cfg);
+            dual_role.push_back((dual_role_cfg, options.clone()));
+        }
+    }
+
+    /// Name of the current file and an unsaved changes marker in front of the app name
+    fn window_title(&self) -> String {
+        let marker = if self.dirty { "• " } else { "" };
+        match self.current_path.as_ref().and_then(|p| p.file_name()) {
+            Some(name) => format!("{marker}{} — evremap config editor", name.to_string_lossy()),
+            None => format!("{marker}evremap config editor"),
+        }
+    }
+
+    /// Page title with the number of entries in the config, like "Editor (3 remaps, 1 dual-role)"
+    fn editor_page_title(&self) -> String {
+        let remaps = self.remaps.len();
+        let dual_role = self.dual_role_remaps.len();
+        if remaps == 0 && dual_role == 0 {
+            return "Editor".to_owned();
+        }
+        let plural = if remaps == 1 { "" } else { "s" };
+        format!("Editor ({remaps} remap{plural}, {dual_role} dual-role)")
+    }
+
+    /// Page title with the number of found devices, without it until the first scan is done
+    fn devices_page_title(&self) -> String {
+        if self.devices.is_empty() {
+            "Devices".to_owned()
+        } else {
+            format!("Devices ({})", self.devices.len())
+        }
+    }
+
+    /// Confirm the opened file was loaded, the editor rebuilding the entries is easy to miss
+    fn show_loaded_summary(
+        &self,
+        path: &Path,
+        config_file: &ConfigFile,
+        source: Option<&ConfigSource>,
+    ) {
+        let file_name = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy();
+        let mut summary = format!(
+            "Loaded {} remaps and {} dual-role entries from {file_name}",
+            config_file.remap.len(),
+            config_file.dual_role.len()
+        );
+        let unknown = source.map(ConfigSource::unknown_keys).unwrap_or_default();
+        if !unknown.is_empty() {
+            summary += &format!(
+                ". Keys the editor doesn't support are kept when saving: {}",
+                unknown.join(", ")
+            );
+        }
+        self.show_message_toast(summary);
+    }
+
+    /// Summarize the problems found in an opened config in a toast, the full list goes to the log
+    fn show_config_warnings(&self, config_file: &ConfigFile) {
+        const SHOWN_WARNINGS: usize = 3;
+        let warnings = config_file.validate();
+        if warnings.is_empty() {
+            return;
+        }
+        for warning in &warnings {
+            log::warn!("Opened config: {warning}");
+        }
+        let mut summary = warnings
+            .iter()
+            .take(SHOWN_WARNINGS)
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ");
+        if warnings.len() > SHOWN_WARNINGS {
+            summary += &format!(" and {} more", warnings.len() - SHOWN_WARNINGS);
         }
+        self.show_message_toast(format!(
+            "The config has problems evremap may reject: {summary}"
+        ));
+    }
+
+    fn append_test_log(&self, line: &str) {
+        self.test_log
+            .insert(&mut self.test_log.end_iter(), &format!("{line}\n"));
+    }
+
+    /// Remember the state before an edit for undo and update the unsaved changes marker
+    fn mark_edited(&mut self) {
+        self.typing = false;
+        self.record_edit();
+    }
+
+    /// Like [`Self::mark_edited`], but text typed in one go, like into the device name or a
+    /// remap comment, is undone at once rather than a character at a time
+    fn mark_text_edited(&mut self) {
+        if self.typing {
+            let current = self.to_config_file();
+            self.update_dirty(&current);
+            self.history_snapshot = current;
+        } else {
+            // Change notifications queued while loading a config don't start typing
+            self.typing = self.record_edit();
+        }
+    }
+
+    /// Push the last snapshot to the undo stack if the config changed since it was taken, returns
+    /// whether it did
+    fn record_edit(&mut self) -> bool {
+        let current = self.to_config_file();
+        self.update_dirty(&current);
+        if current == self.history_snapshot {
+            return false;
+        }
+        self.undo_stack
+            .push(std::mem::replace(&mut self.history_snapshot, current));
+        if self.undo_stack.len() > MAX_UNDO_STEPS {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+        true
+    }
+
+    /// The config has unsaved changes unless it's the same as when it was last opened or saved
+    fn update_dirty(&mut self, current: &ConfigFile) {
+        self.dirty = self.saved_config.as_ref() != Some(current);
+    }
+
+    /// Forget the undo history, the current config becomes the starting point
+    fn reset_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.history_snapshot = self.to_config_file();
+        self.typing = false;
+    }
+
+    /// Load a config from the undo history
+    fn restore(&mut self, config_file: ConfigFile) {
+        self.typing = false;
+        self.update_dirty(&config_file);
+        self.load(config_file);
+        self.update_observed_hints();
+        self.update_remap_conflicts();
     }
 
     /// Collect the data from buffers and factories to form a config file for saving
@@ -599,23 +2682,13 @@ impl AppModel {
     }
 
     fn remaps_extract(&self) -> Vec<RemapConfig> {
-        self.remaps
-            .iter()
-            .map(|remap_item| RemapConfig {
-                input: remap_item.input_seq.model().sequence.to_keys(),
-                output: remap_item.output_seq.model().sequence.to_keys(),
-            })
-            .collect()
+        self.remaps.iter().map(RemapItem::to_config).collect()
     }
 
     fn dual_remaps_extract(&self) -> Vec<DualRoleConfig> {
         self.dual_role_remaps
             .iter()
-            .map(|dual_role| DualRoleConfig {
-                input: dual_role.key,
-                hold: dual_role.hold_seq.model().sequence.to_keys(),
-                tap: dual_role.tap_seq.model().sequence.to_keys(),
-            })
+            .map(DualRoleMapItem::to_config)
             .collect()
     }
 
@@ -628,15 +2701,147 @@ impl AppModel {
                 format!("Error occured: {error}")
             }
         };
+        // Most errors already include their source in the message, only offer the details if
+        // they tell something new
+        let mut causes = error_causes(error.as_ref());
+        causes.retain(|cause| !error_msg.contains(cause.as_str()));
         let toast = adw::Toast::builder()
             .title(&error_msg)
-            .button_label("Dismiss")
+            .button_label(if causes.is_empty() {
+                "Dismiss"
+            } else {
+                "Details"
+            })
             .timeout(10)
             .build();
-        toast.connect_button_clicked(move |tst| tst.dismiss());
+        if causes.is_empty() {
+            toast.connect_button_clicked(move |tst| tst.dismiss());
+        } else {
+            let parent = self.toaster.overlay_widget().clone();
+            toast.connect_button_clicked(move |tst| {
+                tst.dismiss();
+                let details =
+                    causes
+                        .iter()
+                        .fold(format!("{error_msg}\n\nCaused by:"), |mut acc, cause| {
+                            acc.push_str("\n  ");
+                            acc.push_str(cause);
+                            acc
+                        });
+                let dialog = adw::AlertDialog::new(Some("Error details"), Some(&details));
+                dialog.add_response("close", "Close");
+                dialog.present(Some(&parent));
+            });
+        }
         self.toaster.add_toast(toast);
     }
 
+    /// Let the user pick which entries of `config` to add to the editor. Entries that already exist
+    /// are marked and unchecked by default.
+    fn show_import_dialog(
+        &self,
+        root: &gtk::Window,
+        sender: &ComponentSender<Self>,
+        config: ConfigFile,
+    ) {
+        let existing_remaps = self.remaps_extract();
+        let existing_dual_role = self.dual_remaps_extract();
+
+        let list = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        let new_check = |label: String, exists: bool| {
+            let check = gtk::CheckButton::with_label(&label);
+            check.set_active(!exists);
+            if exists {
+                check.set_label(Some(&format!("{label} (already exists)")));
+                check.add_css_class("warning");
+            }
+            list.append(&check);
+            check
+        };
+        let remap_checks: Vec<(gtk::CheckButton, RemapConfig)> = config
+            .remap
+            .into_iter()
+            .map(|remap| {
+                let exists = existing_remaps.iter().any(|e| e.same_as(&remap));
+                (new_check(remap.summary(), exists), remap)
+            })
+            .collect();
+        let dual_role_checks: Vec<(gtk::CheckButton, DualRoleConfig)> = config
+            .dual_role
+            .into_iter()
+            .map(|dual_role| {
+                let exists = existing_dual_role.iter().any(|e| e.same_as(&dual_role));
+                (new_check(dual_role.summary(), exists), dual_role)
+            })
+            .collect();
+
+        if remap_checks.is_empty() && dual_role_checks.is_empty() {
+            self.show_message_toast("Selected config has no entries to import".to_owned());
+            return;
+        }
+
+        let scroll = gtk::ScrolledWindow::builder()
+            .child(&list)
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .min_content_height(200)
+            .build();
+        let dialog = adw::AlertDialog::new(
+            Some("Import entries"),
+            Some("Select the remap and dual-role entries to add to the editor"),
+        );
+        dialog.set_extra_child(Some(&scroll));
+        dialog.add_responses(&[("cancel", "Cancel"), ("import", "Import")]);
+        dialog.set_response_appearance("import", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("import"));
+        dialog.set_close_response("cancel");
+        let sender = sender.clone();
+        dialog.choose(root, None::<&gtk::gio::Cancellable>, move |response| {
+            if response != "import" {
+                return;
+            }
+            sender.input(AppMsg::ImportEntries {
+                remaps: remap_checks
+                    .into_iter()
+                    .filter(|(check, _)| check.is_active())
+                    .map(|(_, remap)| remap)
+                    .collect(),
+                dual_role: dual_role_checks
+                    .into_iter()
+                    .filter(|(check, _)| check.is_active())
+                    .map(|(_, dual_role)| dual_role)
+                    .collect(),
+            });
+        });
+    }
+
+    /// Ask the user to confirm a destructive action before sending `msg`, or send it right away if
+    /// this kind of confirmation is turned off in the preferences
+    fn confirm_destructive(
+        &self,
+        root: &gtk::Window,
+        sender: &ComponentSender<Self>,
+        enabled: bool,
+        heading: &str,
+        body: Option<String>,
+        msg: AppMsg,
+    ) {
+        if !enabled {
+            sender.input(msg);
+            return;
+        }
+        let dialog = adw::AlertDialog::new(Some(heading), body.as_deref());
+        dialog.add_responses(&[("cancel", "Cancel"), ("confirm", "Confirm")]);
+        dialog.set_response_appearance("confirm", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+        let sender = sender.clone();
+        dialog.choose(root, None::<&gtk::gio::Cancellable>, move |response| {
+            if response == "confirm" {
+                sender.input(msg);
+            }
+        });
+    }
+
     fn show_message_toast(&self, msg: String) {
         let toast = adw::Toast::builder()
             .title(msg)
@@ -647,11 +2852,94 @@ impl AppModel {
         self.toaster.add_toast(toast);
     }
 
+    /// Short confirmation that goes away on its own, for things that need no reaction
+    fn show_info_toast(&self, msg: String) {
+        let toast = adw::Toast::builder().title(msg).timeout(2).build();
+        self.toaster.add_toast(toast);
+    }
+
+    /// Focus the remap entry next to the one containing the focus, or the first one if the focus
+    /// is elsewhere. Stops at the first and last entries instead of wrapping around.
+    fn focus_remap(&self, root: &gtk::Window, direction: gtk::DirectionType) {
+        let entries: Vec<gtk::Widget> = std::iter::successors(
+            self.remaps.widget().first_child(),
+            gtk::Widget::next_sibling,
+        )
+        .collect();
+        let current = GtkWindowExt::focus(root)
+            .and_then(|focus| entries.iter().position(|e| focus.is_ancestor(e)));
+        let target = match (current, direction) {
+            (None, _) => 0,
+            (Some(idx), gtk::DirectionType::Up) => idx.saturating_sub(1),
+            (Some(idx), _) => idx + 1,
+        };
+        if let Some(entry) = entries.get(target) {
+            entry.child_focus(gtk::DirectionType::TabForward);
+        }
+    }
+
     /// Display the warning about the device name if there are multiple devices with this name
     /// connected AND phys is not specified.
     fn should_display_name_warning(&self) -> bool {
-        self.duplicate_names
-            .contains(self.config.name.text().as_str())
-            && self.config.phys.text().is_empty()
+        self.duplicate_names.contains(&self.config.name_text())
+            && self.config.phys_text().is_empty()
+    }
+
+    /// Dual-role trigger keys that are also part of some remap's input
+    fn trigger_collisions(&self) -> Vec<KeyCode> {
+        let remap_inputs: HashSet<KeyCode> = self
+            .remaps
+            .iter()
+            .flat_map(|r| r.input_seq.model().sequence.to_keys())
+            .collect();
+        let mut collisions: Vec<KeyCode> = self
+            .dual_role_remaps
+            .iter()
+            .map(|d| d.key)
+            .filter(|k| remap_inputs.contains(k))
+            .collect();
+        collisions.sort_by_key(|k| *k as u32);
+        collisions.dedup();
+        collisions
     }
 }
+
+/// Swap an entry with its neighbor above or below, returns `false` if it's already at that end
+fn move_entry<C: FactoryComponent<Index = DynamicIndex>>(
+    entries: &mut FactoryVecDeque<C>,
+    index: &DynamicIndex,
+    direction: gtk::DirectionType,
+) -> bool {
+    let current = index.current_index();
+    let target = match direction {
+        gtk::DirectionType::Up => current.checked_sub(1),
+        _ => Some(current + 1).filter(|&t| t < entries.len()),
+    };
+    let Some(target) = target else {
+        return false;
+    };
+    entries.guard().swap(current, target);
+    true
+}
+
+/// File types of the open and save dialogs. TOML comes first, as that's what evremap reads.
+fn config_file_filters() -> Vec<gtk::FileFilter> {
+    let toml = gtk::FileFilter::new();
+    toml.set_name(Some("evremap config (TOML)"));
+    toml.add_suffix("toml");
+    let json = gtk::FileFilter::new();
+    json.set_name(Some("JSON, for other tools only (evremap can't read it)"));
+    json.add_suffix("json");
+    let all = gtk::FileFilter::new();
+    all.set_name(Some("All files"));
+    all.add_pattern("*");
+    vec![toml, json, all]
+}
+
+/// Collect the messages of all the underlying errors in the source chain, excluding the error
+/// itself
+fn error_causes(error: &(dyn Error + 'static)) -> Vec<String> {
+    std::iter::successors(error.source(), |&e| e.source())
+        .map(|e| e.to_string())
+        .collect()
+}
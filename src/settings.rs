@@ -0,0 +1,342 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use evdev_rs::enums::int_to_ev_key;
+use gtk::gio::{self, prelude::*};
+use thiserror::Error;
+
+use crate::{
+    APP_ID,
+    components::{device_browser::DeviceFilter, event_logger::DEFAULT_MAX_LOG_LINES},
+    deviceinfo::{DeviceId, DeviceKindFilter, DeviceSort},
+    evdev_utils::KeyCode,
+};
+
+/// How many recently opened or saved config files are remembered
+const MAX_RECENT_FILES: usize = 10;
+
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error(
+        "The settings schema {} is not installed, settings won't be kept",
+        APP_ID
+    )]
+    SchemaMissing,
+    #[error("Settings are not writable: {0}")]
+    NotWritable(#[from] glib::BoolError),
+}
+
+/// Persistent application settings, stored with GSettings under the [`APP_ID`] schema from
+/// `data/ru.jtcf.evremap_gtk.gschema.xml`. They are read once at startup and written back
+/// whole when something changes.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// Start without the device browser and the event logger, same as `--editor-only`
+    pub editor_only: bool,
+    /// Show key names without their numeric evdev codes
+    pub hide_key_codes: bool,
+    pub device_browser: DeviceBrowserSettings,
+    pub confirmations: ConfirmationSettings,
+    pub saving: SavingSettings,
+    pub event_logger: EventLoggerSettings,
+    pub window: WindowSettings,
+    /// Friendly names for devices
+    pub device_aliases: HashMap<DeviceId, String>,
+    /// Key sequences saved by the user to be inserted into any sequence
+    pub key_presets: Vec<KeyPreset>,
+    /// Device selected in the event logger when the app was last used
+    pub last_logger_device: Option<DeviceId>,
+    /// Config files recently opened or saved, the most recent first
+    pub recent_files: Vec<PathBuf>,
+}
+
+/// State of the device browser applied after the first device scan
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceBrowserSettings {
+    pub sort: DeviceSort,
+    pub kind: DeviceKindFilter,
+    /// Rescan the devices when one is plugged in or removed
+    pub auto_refresh: bool,
+}
+
+impl Default for DeviceBrowserSettings {
+    fn default() -> Self {
+        Self {
+            sort: DeviceSort::default(),
+            kind: DeviceKindFilter::default(),
+            auto_refresh: true,
+        }
+    }
+}
+
+impl DeviceBrowserSettings {
+    pub fn filter(&self) -> DeviceFilter {
+        DeviceFilter {
+            kind: self.kind,
+            ..Default::default()
+        }
+    }
+}
+
+/// Which destructive actions ask for confirmation first
+#[derive(Debug, Clone, Copy)]
+pub struct ConfirmationSettings {
+    /// Deleting a remap or dual-role entry
+    pub delete_entry: bool,
+    /// Deleting all remaps or all dual-role entries at once
+    pub clear_all: bool,
+    /// Reloading the file, losing the unsaved changes and the undo history
+    pub revert: bool,
+    /// Closing the window with unsaved changes, otherwise they are discarded
+    pub discard_on_close: bool,
+}
+
+impl Default for ConfirmationSettings {
+    fn default() -> Self {
+        Self {
+            delete_entry: true,
+            clear_all: true,
+            revert: true,
+            discard_on_close: true,
+        }
+    }
+}
+
+/// What happens when a config is written to a file
+#[derive(Debug, Clone, Copy)]
+pub struct SavingSettings {
+    /// Copy the file to `<file>.bak` before overwriting it
+    pub backup: bool,
+}
+
+impl Default for SavingSettings {
+    fn default() -> Self {
+        Self { backup: true }
+    }
+}
+
+/// Event logger options also available on its page
+#[derive(Debug, Clone, Copy)]
+pub struct EventLoggerSettings {
+    /// The oldest lines are removed from the log past this count
+    pub max_lines: u32,
+}
+
+impl Default for EventLoggerSettings {
+    fn default() -> Self {
+        Self {
+            max_lines: DEFAULT_MAX_LOG_LINES,
+        }
+    }
+}
+
+/// Window size and the visible page restored on the next launch
+#[derive(Debug, Clone)]
+pub struct WindowSettings {
+    pub width: i32,
+    pub height: i32,
+    pub maximized: bool,
+    /// Name of the page of the main stack that was visible last
+    pub page: Option<String>,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            width: 600,
+            height: 400,
+            maximized: false,
+            page: None,
+        }
+    }
+}
+
+/// Named key sequence that can be inserted into remaps and dual-role entries
+#[derive(Debug, Clone)]
+pub struct KeyPreset {
+    pub name: String,
+    pub keys: Vec<KeyCode>,
+}
+
+impl Settings {
+    pub fn device_alias(&self, id: &DeviceId) -> Option<&str> {
+        self.device_aliases.get(id).map(String::as_str)
+    }
+
+    /// Assign an alias to a device, or remove it if `alias` is [`None`]
+    pub fn set_device_alias(&mut self, id: DeviceId, alias: Option<String>) {
+        match alias {
+            Some(alias) => self.device_aliases.insert(id, alias),
+            None => self.device_aliases.remove(&id),
+        };
+    }
+
+    /// Add the preset, replacing the one with the same name
+    pub fn save_key_preset(&mut self, preset: KeyPreset) {
+        self.remove_key_preset(&preset.name);
+        self.key_presets.push(preset);
+    }
+
+    pub fn remove_key_preset(&mut self, name: &str) {
+        self.key_presets.retain(|p| p.name != name);
+    }
+
+    /// Put the file at the top of the recent files, dropping the oldest ones past the limit
+    pub fn add_recent_file(&mut self, path: PathBuf) {
+        self.remove_recent_file(&path);
+        self.recent_files.insert(0, path);
+        self.recent_files.truncate(MAX_RECENT_FILES);
+    }
+
+    pub fn remove_recent_file(&mut self, path: &Path) {
+        self.recent_files.retain(|p| p != path);
+    }
+
+    /// Read the settings, falling back to the defaults if the schema isn't installed
+    pub fn load() -> Result<Self, SettingsError> {
+        let gsettings = gsettings()?;
+        let page = gsettings.string("last-page");
+        // The schema guarantees the types, the defaults are there just in case
+        let aliases: Vec<(String, Option<String>, String)> =
+            gsettings.value("device-aliases").get().unwrap_or_default();
+        let presets: Vec<(String, Vec<u32>)> =
+            gsettings.value("key-presets").get().unwrap_or_default();
+        let last_logger_device: Option<(String, Option<String>)> =
+            gsettings.value("last-logger-device").get().flatten();
+        let recent_files: Vec<String> = gsettings.value("recent-files").get().unwrap_or_default();
+        Ok(Self {
+            editor_only: gsettings.boolean("editor-only"),
+            hide_key_codes: gsettings.boolean("hide-key-codes"),
+            device_browser: DeviceBrowserSettings {
+                sort: enum_setting(&gsettings, "device-sort", &DeviceSort::ALL),
+                kind: enum_setting(&gsettings, "device-kind", &DeviceKindFilter::ALL),
+                auto_refresh: gsettings.boolean("auto-refresh-devices"),
+            },
+            confirmations: ConfirmationSettings {
+                delete_entry: gsettings.boolean("confirm-delete-entry"),
+                clear_all: gsettings.boolean("confirm-clear-all"),
+                revert: gsettings.boolean("confirm-revert"),
+                discard_on_close: gsettings.boolean("confirm-discard-on-close"),
+            },
+            saving: SavingSettings {
+                backup: gsettings.boolean("backup-on-save"),
+            },
+            event_logger: EventLoggerSettings {
+                max_lines: gsettings.uint("log-max-lines"),
+            },
+            window: WindowSettings {
+                width: gsettings.int("window-width"),
+                height: gsettings.int("window-height"),
+                maximized: gsettings.boolean("window-maximized"),
+                page: (!page.is_empty()).then(|| page.to_string()),
+            },
+            device_aliases: aliases
+                .into_iter()
+                .map(|(name, phys, alias)| (DeviceId { name, phys }, alias))
+                .collect(),
+            // Codes this system doesn't know are left out
+            key_presets: presets
+                .into_iter()
+                .map(|(name, codes)| KeyPreset {
+                    name,
+                    keys: codes.into_iter().filter_map(int_to_ev_key).collect(),
+                })
+                .collect(),
+            last_logger_device: last_logger_device.map(|(name, phys)| DeviceId { name, phys }),
+            recent_files: recent_files.into_iter().map(PathBuf::from).collect(),
+        })
+    }
+
+    pub fn save(&self) -> Result<(), SettingsError> {
+        let gsettings = gsettings()?;
+        // Write all the keys at once on apply
+        gsettings.delay();
+        gsettings.set_boolean("editor-only", self.editor_only)?;
+        gsettings.set_boolean("hide-key-codes", self.hide_key_codes)?;
+
+        let browser = &self.device_browser;
+        set_enum_setting(&gsettings, "device-sort", &DeviceSort::ALL, browser.sort)?;
+        set_enum_setting(
+            &gsettings,
+            "device-kind",
+            &DeviceKindFilter::ALL,
+            browser.kind,
+        )?;
+        gsettings.set_boolean("auto-refresh-devices", browser.auto_refresh)?;
+
+        let confirmations = &self.confirmations;
+        gsettings.set_boolean("confirm-delete-entry", confirmations.delete_entry)?;
+        gsettings.set_boolean("confirm-clear-all", confirmations.clear_all)?;
+        gsettings.set_boolean("confirm-revert", confirmations.revert)?;
+        gsettings.set_boolean("confirm-discard-on-close", confirmations.discard_on_close)?;
+
+        gsettings.set_boolean("backup-on-save", self.saving.backup)?;
+        gsettings.set_uint("log-max-lines", self.event_logger.max_lines)?;
+
+        let window = &self.window;
+        gsettings.set_int("window-width", window.width)?;
+        gsettings.set_int("window-height", window.height)?;
+        gsettings.set_boolean("window-maximized", window.maximized)?;
+        gsettings.set_string("last-page", window.page.as_deref().unwrap_or_default())?;
+
+        let aliases: Vec<(String, Option<String>, String)> = self
+            .device_aliases
+            .iter()
+            .map(|(id, alias)| (id.name.clone(), id.phys.clone(), alias.clone()))
+            .collect();
+        gsettings.set_value("device-aliases", &aliases.to_variant())?;
+        let presets: Vec<(String, Vec<u32>)> = self
+            .key_presets
+            .iter()
+            .map(|p| (p.name.clone(), p.keys.iter().map(|k| *k as u32).collect()))
+            .collect();
+        gsettings.set_value("key-presets", &presets.to_variant())?;
+        let last_logger_device = self
+            .last_logger_device
+            .as_ref()
+            .map(|dev| (dev.name.clone(), dev.phys.clone()));
+        gsettings.set_value("last-logger-device", &last_logger_device.to_variant())?;
+        let recent_files: Vec<String> = self
+            .recent_files
+            .iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect();
+        gsettings.set_value("recent-files", &recent_files.to_variant())?;
+
+        gsettings.apply();
+        gio::Settings::sync();
+        Ok(())
+    }
+}
+
+/// Settings of the installed schema. Looked up first, because creating [`gio::Settings`] for a
+/// missing schema aborts the app.
+fn gsettings() -> Result<gio::Settings, SettingsError> {
+    let schema = gio::SettingsSchemaSource::default()
+        .and_then(|source| source.lookup(APP_ID, true))
+        .ok_or(SettingsError::SchemaMissing)?;
+    Ok(gio::Settings::new_full(
+        &schema,
+        None::<&gio::SettingsBackend>,
+        None,
+    ))
+}
+
+/// Value of an enum key, whose values in the schema are the positions in `all`
+fn enum_setting<T: Copy + Default>(gsettings: &gio::Settings, key: &str, all: &[T]) -> T {
+    all.get(gsettings.enum_(key) as usize)
+        .copied()
+        .unwrap_or_default()
+}
+
+fn set_enum_setting<T: PartialEq>(
+    gsettings: &gio::Settings,
+    key: &str,
+    all: &[T],
+    value: T,
+) -> Result<(), glib::BoolError> {
+    let position = all.iter().position(|v| *v == value).unwrap_or_default();
+    gsettings.set_enum(key, position as i32)
+}